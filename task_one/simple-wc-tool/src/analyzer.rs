@@ -1,56 +1,399 @@
-use anyhow::{Context, Result};
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use crate::entities::{Entities, EntityCounter};
+use crate::error::AnalyzerError;
+use crate::language::{Detection, LanguageDetector};
+use crate::markup::{Markup, MarkupFilter};
+use crate::metric::{self, Chars, Lines, Metric, Words};
+use crate::normalization::Normalization;
+use crate::region::{self, Region};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
+use std::ops::ControlFlow;
 use std::path::Path;
 
-#[derive(Debug, PartialEq)]
+type Result<T> = std::result::Result<T, AnalyzerError>;
+
+/// Called with the size of every chunk read from a file. Breaking stops reading there,
+/// and the file is counted as far as it was read.
+pub type OnChunk<'a> = dyn FnMut(usize) -> ControlFlow<()> + 'a;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct FileStats {
     pub words: usize,
     pub lines: usize,
     pub chars: usize,
 }
 
+impl FileStats {
+    /// Names accepted by [`FileStats::metric`].
+    pub const METRICS: [&'static str; 3] = ["words", "lines", "chars"];
+
+    /// Looks up a metric by name.
+    pub fn metric(&self, name: &str) -> Option<usize> {
+        match name {
+            "words" => Some(self.words),
+            "lines" => Some(self.lines),
+            "chars" => Some(self.chars),
+            _ => None,
+        }
+    }
+}
+
+impl std::ops::Add for FileStats {
+    type Output = FileStats;
+
+    fn add(self, other: FileStats) -> FileStats {
+        FileStats {
+            words: self.words + other.words,
+            lines: self.lines + other.lines,
+            chars: self.chars + other.chars,
+        }
+    }
+}
+
+/// Everything collected about one input.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Analysis {
+    #[serde(flatten)]
+    pub stats: FileStats,
+    /// Content of code blocks, which markup-aware counting keeps out of `stats`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<FileStats>,
+    /// Values of the metrics registered with [`Analyzer::add_metric`], by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: BTreeMap<String, usize>,
+    /// With normalization, whether the input already was in the requested form.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalized: Option<bool>,
+    /// With language detection, the likeliest language of the prose.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Detection>,
+    /// With entity counting, the emoji, URLs and other entities of the prose.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entities: Option<Entities>,
+}
+
+/// The built-in metrics behind [`FileStats`].
+#[derive(Debug, Default, Clone)]
+struct Counts {
+    words: Words,
+    lines: Lines,
+    chars: Chars,
+}
+
+impl Counts {
+    fn add_line(&mut self, line: &str) {
+        let first = self.lines.value() == 0;
+        metric::feed_line(&mut self.words, line, first);
+        metric::feed_line(&mut self.lines, line, first);
+        metric::feed_line(&mut self.chars, line, first);
+    }
+
+    fn stats(&self) -> FileStats {
+        FileStats {
+            words: self.words.value(),
+            lines: self.lines.value(),
+            chars: self.chars.value(),
+        }
+    }
+}
+
+/// Incremental analyzer that can be fed content in arbitrary chunks.
+///
+/// Lines are counted the same way `BufRead::lines` splits them: a trailing newline
+/// doesn't start a new line, and `\r\n` counts as a single separator character.
+/// A chunk may end in the middle of a line or even of a UTF-8 sequence; the tail is
+/// kept until the rest of it arrives, so counting can be resumed as the input grows.
+///
+/// With markup stripping enabled, only the visible prose is counted in the main stats
+/// and code blocks are counted separately. Registered metrics see the same prose lines.
+///
+/// With a [`Normalization`], every line is normalized before anything else looks at it.
+/// Normalization never crosses a line break, so this equals normalizing the whole input.
+#[derive(Default)]
+pub struct Analyzer {
+    counts: Counts,
+    pending: Vec<u8>,
+    /// Offset of the first byte of the current line in the input.
+    line_start: u64,
+    markup: Option<MarkupFilter>,
+    code: Counts,
+    metrics: Vec<Box<dyn Metric>>,
+    normalization: Option<Normalization>,
+    /// Whether every line so far already was in normalized form.
+    was_normalized: bool,
+    language: Option<LanguageDetector>,
+    entities: Option<EntityCounter>,
+}
+
+impl fmt::Debug for Analyzer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metrics: Vec<&str> = self.metrics.iter().map(|m| m.name()).collect();
+        f.debug_struct("Analyzer")
+            .field("counts", &self.counts)
+            .field("pending", &self.pending.len())
+            .field("markup", &self.markup)
+            .field("code", &self.code)
+            .field("metrics", &metrics)
+            .field("normalization", &self.normalization)
+            .field("language", &self.language.is_some())
+            .field("entities", &self.entities.is_some())
+            .finish()
+    }
+}
+
+impl Analyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_markup(markup: Option<Markup>) -> Self {
+        Analyzer {
+            markup: markup.map(MarkupFilter::new),
+            ..Self::default()
+        }
+    }
+
+    /// Normalizes the input to `form` before counting.
+    pub fn set_normalization(&mut self, form: Normalization) {
+        self.normalization = Some(form);
+        self.was_normalized = true;
+    }
+
+    /// Also identifies the language of the prose, see [`LanguageDetector`].
+    pub fn detect_language(&mut self) {
+        self.language = Some(LanguageDetector::new());
+    }
+
+    /// Also counts entities such as emoji and URLs in the prose, see [`EntityCounter`].
+    pub fn count_entities(&mut self) {
+        self.entities = Some(EntityCounter::new());
+    }
+
+    /// Registers a custom metric, computed alongside words, lines and chars.
+    pub fn add_metric(&mut self, metric: impl Metric + 'static) {
+        self.metrics.push(Box::new(metric));
+    }
+
+    /// Feeds the next chunk of content. Fails with [`AnalyzerError::InvalidUtf8`] as
+    /// soon as a complete line isn't valid UTF-8.
+    pub fn update(&mut self, mut data: &[u8]) -> Result<()> {
+        for metric in &mut self.metrics {
+            metric.chunk(data);
+        }
+        while let Some(pos) = data.iter().position(|&b| b == b'\n') {
+            let len = self.pending.len() + pos;
+            if self.pending.is_empty() {
+                self.add_line(&data[..pos])?;
+            } else {
+                self.pending.extend_from_slice(&data[..pos]);
+                let line = std::mem::take(&mut self.pending);
+                self.add_line(&line)?;
+            }
+            self.line_start += len as u64 + 1;
+            data = &data[pos + 1..];
+        }
+        self.pending.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// Statistics for everything fed so far, counting an unterminated last line
+    /// up to its last complete character.
+    pub fn stats(&self) -> FileStats {
+        let mut counts = self.counts.clone();
+        if !self.pending.is_empty() {
+            let valid = match std::str::from_utf8(&self.pending) {
+                Ok(text) => text,
+                Err(e) => std::str::from_utf8(&self.pending[..e.valid_up_to()]).unwrap_or(""),
+            };
+            let valid = match self.normalization {
+                Some(form) => form.apply(valid),
+                None => valid.into(),
+            };
+            let valid = valid.as_ref();
+            match &self.markup {
+                Some(filter) => {
+                    if let Some(prose) = filter.clone().filter(valid).prose {
+                        counts.add_line(&prose);
+                    }
+                }
+                None => counts.add_line(valid),
+            }
+        }
+        counts.stats()
+    }
+
+    /// Finishes the input, counting an unterminated last line.
+    pub fn finish(mut self) -> Result<Analysis> {
+        if !self.pending.is_empty() {
+            let line = std::mem::take(&mut self.pending);
+            self.add_terminated_line(&line)?;
+        }
+        Ok(Analysis {
+            stats: self.counts.stats(),
+            code: self.markup.is_some().then(|| self.code.stats()),
+            metrics: self
+                .metrics
+                .iter()
+                .map(|metric| (metric.name().to_string(), metric.value()))
+                .collect(),
+            normalized: self.normalization.map(|_| self.was_normalized),
+            language: self.language.as_ref().and_then(LanguageDetector::detect),
+            entities: self.entities.as_ref().map(EntityCounter::entities),
+        })
+    }
+
+    /// Finishes input that was cut off before its end, leaving out a character that was
+    /// only partly read.
+    pub fn finish_partial(mut self) -> Result<Analysis> {
+        if let Err(e) = std::str::from_utf8(&self.pending) {
+            if e.error_len().is_none() {
+                self.pending.truncate(e.valid_up_to());
+            }
+        }
+        self.finish()
+    }
+
+    /// Forgets everything fed so far, e.g. after the input was truncated.
+    /// Registered metrics are reset with [`Metric::reset`].
+    pub fn reset(&mut self) {
+        self.counts = Counts::default();
+        self.code = Counts::default();
+        self.pending.clear();
+        self.line_start = 0;
+        self.was_normalized = true;
+        if let Some(detector) = &mut self.language {
+            *detector = LanguageDetector::new();
+        }
+        if let Some(counter) = &mut self.entities {
+            *counter = EntityCounter::new();
+        }
+        if let Some(filter) = &mut self.markup {
+            *filter = MarkupFilter::new(filter.markup());
+        }
+        for metric in &mut self.metrics {
+            metric.reset();
+        }
+    }
+
+    fn add_line(&mut self, line: &[u8]) -> Result<()> {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        self.add_terminated_line(line)
+    }
+
+    fn add_terminated_line(&mut self, line: &[u8]) -> Result<()> {
+        let text = std::str::from_utf8(line).map_err(|e| AnalyzerError::InvalidUtf8 {
+            path: None,
+            offset: self.line_start + e.valid_up_to() as u64,
+        })?;
+        let normalized;
+        let text = match self.normalization {
+            Some(form) => {
+                normalized = form.apply(text);
+                self.was_normalized &= matches!(normalized, std::borrow::Cow::Borrowed(_));
+                normalized.as_ref()
+            }
+            None => text,
+        };
+        match &mut self.markup {
+            Some(filter) => {
+                let visible = filter.filter(text);
+                if let Some(prose) = visible.prose {
+                    self.add_prose_line(&prose);
+                }
+                if let Some(code) = visible.code {
+                    self.code.add_line(&code);
+                }
+            }
+            None => self.add_prose_line(text),
+        }
+        Ok(())
+    }
+
+    fn add_prose_line(&mut self, line: &str) {
+        let first = self.counts.lines.value() == 0;
+        for metric in &mut self.metrics {
+            metric::feed_line(metric.as_mut(), line, first);
+        }
+        if let Some(detector) = &mut self.language {
+            detector.chars(line);
+            detector.chars("\n");
+        }
+        if let Some(counter) = &mut self.entities {
+            counter.line(line);
+        }
+        self.counts.add_line(line);
+    }
+}
+
 /// Analyzes a file and returns statistics about its content.
 ///
 /// Note: Could be optimized using Rayon for large files parallel processing,
 /// but it would be an overkill for a test task. Also, I haven't got enough time to analyze if it's worth it.
 pub fn analyze_file(path: &Path) -> Result<FileStats> {
-    let file =
-        File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
-    let reader = BufReader::new(file);
+    analyze_file_with(path, Analyzer::new(), None).map(|analysis| analysis.stats)
+}
 
-    let mut stats = FileStats {
-        words: 0,
-        lines: 0,
-        chars: 0,
-    };
+/// Analyzes the selected region of a file with a configured analyzer.
+pub fn analyze_file_with(
+    path: &Path,
+    analyzer: Analyzer,
+    region: Option<&Region>,
+) -> Result<Analysis> {
+    analyze_file_with_progress(path, analyzer, region, &mut |_| ControlFlow::Continue(()))
+}
 
-    let mut is_first_line = true;
+/// Like [`analyze_file_with`], reporting every chunk read to `on_chunk`.
+pub fn analyze_file_with_progress(
+    path: &Path,
+    analyzer: Analyzer,
+    region: Option<&Region>,
+    on_chunk: &mut OnChunk,
+) -> Result<Analysis> {
+    let file = region::open(path, region)?;
 
-    for line in reader.lines() {
-        let line =
-            line.with_context(|| format!("Failed to read line from file: {}", path.display()))?;
+    analyze_reader_with_progress(file, analyzer, on_chunk).map_err(|e| e.with_path(path))
+}
 
-        if !is_first_line {
-            stats.chars += 1;
-        }
-        is_first_line = false;
+/// Feeds everything readable from `reader` to `analyzer`.
+pub fn analyze_reader<R: Read>(reader: R, analyzer: Analyzer) -> Result<Analysis> {
+    analyze_reader_with_progress(reader, analyzer, &mut |_| ControlFlow::Continue(()))
+}
+
+/// Like [`analyze_reader`], reporting every chunk read to `on_chunk`, which can stop
+/// reading early.
+pub fn analyze_reader_with_progress<R: Read>(
+    reader: R,
+    mut analyzer: Analyzer,
+    on_chunk: &mut OnChunk,
+) -> Result<Analysis> {
+    let mut reader = BufReader::new(reader);
 
-        stats.lines += 1;
-        stats.words += count_words(&line);
-        stats.chars += line.chars().count();
+    loop {
+        let chunk = reader.fill_buf().map_err(|e| AnalyzerError::io(None, e))?;
+        if chunk.is_empty() {
+            break;
+        }
+        let len = chunk.len();
+        analyzer.update(chunk)?;
+        reader.consume(len);
+        if on_chunk(len).is_break() {
+            return analyzer.finish_partial();
+        }
     }
 
-    Ok(stats)
+    analyzer.finish()
 }
 
-fn count_words(text: &str) -> usize {
+pub(crate) fn count_words(text: &str) -> usize {
     text.split_whitespace().count()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use anyhow::Result;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
@@ -164,4 +507,129 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_chunked_input_matches_whole_file() -> Result<()> {
+        let content = "héllo wörld\r\nrust is great\n\nthird line\n";
+        let file = create_temp_file(content)?;
+        let expected = analyze_file(file.path())?;
+
+        let mut analyzer = Analyzer::new();
+        for byte in content.as_bytes() {
+            analyzer.update(std::slice::from_ref(byte))?;
+        }
+
+        assert_eq!(analyzer.finish()?.stats, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_counts_unterminated_line() -> Result<()> {
+        let mut analyzer = Analyzer::new();
+        analyzer.update(b"hello\nwor")?;
+
+        assert_eq!(
+            analyzer.stats(),
+            FileStats {
+                words: 2,
+                lines: 2,
+                chars: 9,
+            }
+        );
+
+        analyzer.update(b"ld\n")?;
+        assert_eq!(analyzer.stats().chars, 11);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_restarts_metrics() -> Result<()> {
+        let mut analyzer = Analyzer::new();
+        analyzer.add_metric(metric::Bytes::default());
+        analyzer.update(b"hello world\nsecond")?;
+        analyzer.reset();
+        analyzer.update(b"new\n")?;
+
+        let analysis = analyzer.finish()?;
+        assert_eq!(analysis.stats.lines, 1);
+        assert_eq!(analysis.metrics["bytes"], 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_utf8_offset() {
+        let mut analyzer = Analyzer::new();
+        analyzer.update(b"first\r\nsec").unwrap();
+        let err = analyzer.update(b"ond \xff\n").unwrap_err();
+        assert!(matches!(err, AnalyzerError::InvalidUtf8 { offset: 14, .. }));
+
+        let file = create_temp_file("ok\n").unwrap();
+        let err = analyze_file(&file.path().with_extension("missing")).unwrap_err();
+        assert!(matches!(err, AnalyzerError::NotFound { .. }));
+    }
+
+    #[test]
+    fn test_normalization() -> Result<()> {
+        let analyze = |text: &str| -> Result<Analysis, AnalyzerError> {
+            let mut analyzer = Analyzer::new();
+            analyzer.set_normalization(Normalization::Nfc);
+            analyzer.update(text.as_bytes())?;
+            analyzer.finish()
+        };
+
+        let composed = analyze("caf\u{e9} cr\u{e8}me\n")?;
+        let decomposed = analyze("cafe\u{301} cre\u{300}me\n")?;
+        assert_eq!(composed.stats, decomposed.stats);
+        assert_eq!(composed.stats.chars, 10);
+        assert_eq!(composed.normalized, Some(true));
+        assert_eq!(decomposed.normalized, Some(false));
+        assert_eq!(
+            analyze_reader("x".as_bytes(), Analyzer::new())?.normalized,
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let mut analyzer = Analyzer::new();
+        assert!(analyzer.update(b"ok\n\xff\xfe\n").is_err());
+    }
+
+    #[test]
+    fn test_stops_reading() -> Result<()> {
+        // Hands out two bytes at a time, so "é" is split between chunks.
+        struct Trickle<'a>(&'a [u8]);
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = self.0.len().min(buf.len()).min(2);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+
+        let input = "one\nté\nthree\n".as_bytes();
+        let mut read = 0;
+        let mut on_chunk = |n| {
+            read += n;
+            if read >= 6 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        };
+        let analysis =
+            analyze_reader_with_progress(Trickle(input), Analyzer::new(), &mut on_chunk)?;
+        assert_eq!(read, 6);
+        assert_eq!(
+            analysis.stats,
+            FileStats {
+                words: 2,
+                lines: 2,
+                chars: 5,
+            }
+        );
+        Ok(())
+    }
 }
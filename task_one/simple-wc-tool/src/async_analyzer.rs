@@ -0,0 +1,135 @@
+//! Async counterparts of [`analyze_file`](crate::analyzer::analyze_file) and
+//! [`analyze_reader`](crate::analyzer::analyze_reader), for use inside a tokio runtime.
+//! They feed the same [`Analyzer`], so results are identical to the sync API.
+
+use crate::analyzer::{Analysis, Analyzer, FileStats};
+use crate::error::AnalyzerError;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+type Result<T> = std::result::Result<T, AnalyzerError>;
+
+/// Analyzes a file without blocking the runtime.
+pub async fn analyze_file(path: &Path) -> Result<FileStats> {
+    let file = File::open(path)
+        .await
+        .map_err(|e| AnalyzerError::io(Some(path), e))?;
+
+    analyze_reader(file, Analyzer::new())
+        .await
+        .map(|analysis| analysis.stats)
+        .map_err(|e| e.with_path(path))
+}
+
+/// Feeds everything readable from `reader` to `analyzer`.
+pub async fn analyze_reader<R: AsyncRead + Unpin>(
+    reader: R,
+    mut analyzer: Analyzer,
+) -> Result<Analysis> {
+    let mut reader = BufReader::new(reader);
+
+    loop {
+        let chunk = reader
+            .fill_buf()
+            .await
+            .map_err(|e| AnalyzerError::io(None, e))?;
+        if chunk.is_empty() {
+            break;
+        }
+        let len = chunk.len();
+        analyzer.update(chunk)?;
+        reader.consume(len);
+    }
+
+    analyzer.finish()
+}
+
+/// Analyzes many files, at most `concurrency` at a time. Results are in the order of
+/// `paths`, and a file that fails doesn't stop the others.
+pub async fn analyze_files(paths: &[PathBuf], concurrency: usize) -> Vec<Result<FileStats>> {
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for (index, path) in paths.iter().cloned().enumerate() {
+        let permits = Arc::clone(&permits);
+        tasks.spawn(async move {
+            // The semaphore is never closed, so acquiring can't fail.
+            let _permit = permits.acquire_owned().await;
+            (index, analyze_file(&path).await)
+        });
+    }
+
+    let mut results: Vec<Option<Result<FileStats>>> = paths.iter().map(|_| None).collect();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((index, result)) => results[index] = Some(result),
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(_) => {}
+        }
+    }
+    results
+        .into_iter()
+        .zip(paths)
+        .map(|(result, path)| {
+            result.unwrap_or_else(|| {
+                let cancelled =
+                    io::Error::new(io::ErrorKind::Interrupted, "analysis was cancelled");
+                Err(AnalyzerError::io(Some(path), cancelled))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer;
+    use anyhow::Result;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_matches_sync_analyzer() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let contents = [
+            "Hello, World!\nThis is a test.\n",
+            "no trailing newline\r\nsecond",
+            "",
+            "\n\n\n",
+            "Unicode: привет, мир! 你好, 世界!\n",
+        ];
+        let mut paths = Vec::new();
+        for (i, content) in contents.iter().enumerate() {
+            let path = dir.path().join(format!("{}.txt", i));
+            fs::write(&path, content)?;
+            paths.push(path);
+        }
+        // A long file, so reads span several buffer fills.
+        let long = dir.path().join("long.txt");
+        fs::write(&long, "word ünïcödé\r\n".repeat(5000))?;
+        paths.push(long);
+
+        let results = analyze_files(&paths, 2).await;
+        assert_eq!(results.len(), paths.len());
+        for (path, result) in paths.iter().zip(results) {
+            assert_eq!(result?, analyzer::analyze_file(path)?);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_per_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let good = dir.path().join("good.txt");
+        fs::write(&good, "one two\n")?;
+        let paths = vec![dir.path().join("missing.txt"), good];
+
+        let results = analyze_files(&paths, 4).await;
+        assert!(matches!(results[0], Err(AnalyzerError::NotFound { .. })));
+        assert_eq!(results[1].as_ref().unwrap().words, 2);
+        Ok(())
+    }
+}
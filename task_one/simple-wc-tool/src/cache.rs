@@ -0,0 +1,338 @@
+use crate::analyzer::Analysis;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::UNIX_EPOCH;
+
+/// Cache file name, in the `simple-wc` directory of the user cache directory.
+pub const CACHE_FILE: &str = "stats.json";
+
+/// Bumped whenever the meaning of cached stats changes, which invalidates old caches.
+const FORMAT_VERSION: u32 = 3;
+
+/// What a file looked like when its stats were computed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    /// BLAKE3 of the content, only computed when hash verification is on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+}
+
+impl Fingerprint {
+    pub fn of(path: &Path, with_hash: bool) -> Result<Self> {
+        let metadata = fs::metadata(path)
+            .with_context(|| format!("Failed to read metadata: {}", path.display()))?;
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+
+        let hash = if with_hash {
+            let mut hasher = blake3::Hasher::new();
+            let mut file = File::open(path)
+                .with_context(|| format!("Failed to open file: {}", path.display()))?;
+            io::copy(&mut file, &mut hasher)
+                .with_context(|| format!("Failed to hash file: {}", path.display()))?;
+            Some(hasher.finalize().to_hex().to_string())
+        } else {
+            None
+        };
+
+        Ok(Fingerprint {
+            size: metadata.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+            hash,
+        })
+    }
+
+    /// Size and mtime must match; hashes are compared when both sides have one.
+    /// A hash is required when the lookup was made with one.
+    fn matches(&self, cached: &Fingerprint) -> bool {
+        self.size == cached.size
+            && self.mtime_secs == cached.mtime_secs
+            && self.mtime_nanos == cached.mtime_nanos
+            && match (&self.hash, &cached.hash) {
+                (Some(hash), Some(cached)) => hash == cached,
+                (Some(_), None) => false,
+                (None, _) => true,
+            }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    fingerprint: Fingerprint,
+    /// Options that affect the counts, e.g. the markup being stripped.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    settings: String,
+    analysis: Analysis,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheData {
+    version: u32,
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+/// On-disk cache of per-file stats, so that unchanged files are not read again.
+pub struct Cache {
+    path: PathBuf,
+    data: CacheData,
+    dirty: bool,
+}
+
+impl Cache {
+    /// Loads the cache. A missing, unreadable or outdated cache file starts an empty cache.
+    pub fn load(path: &Path) -> Self {
+        let data = fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<CacheData>(&bytes).ok())
+            .filter(|data| data.version == FORMAT_VERSION)
+            .unwrap_or(CacheData {
+                version: FORMAT_VERSION,
+                entries: BTreeMap::new(),
+            });
+
+        Cache {
+            path: path.to_path_buf(),
+            data,
+            dirty: false,
+        }
+    }
+
+    /// Cached results for `file`, if they were computed from the same content with the
+    /// same `settings`.
+    pub fn get(&self, file: &Path, fingerprint: &Fingerprint, settings: &str) -> Option<Analysis> {
+        let entry = self.data.entries.get(&cache_key(file))?;
+        (fingerprint.matches(&entry.fingerprint) && entry.settings == settings)
+            .then(|| entry.analysis.clone())
+    }
+
+    pub fn insert(
+        &mut self,
+        file: &Path,
+        fingerprint: Fingerprint,
+        settings: &str,
+        analysis: Analysis,
+    ) {
+        let entry = Entry {
+            fingerprint,
+            settings: settings.to_string(),
+            analysis,
+        };
+        self.data.entries.insert(cache_key(file), entry);
+        self.dirty = true;
+    }
+
+    /// Drops entries of files that no longer exist. Returns how many were removed.
+    pub fn prune(&mut self) -> usize {
+        let before = self.data.entries.len();
+        self.data.entries.retain(|path, _| path.is_file());
+        let removed = before - self.data.entries.len();
+        self.dirty |= removed > 0;
+        removed
+    }
+
+    /// Writes the cache back if anything changed.
+    pub fn save(&self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create cache directory: {}", dir.display()))?;
+        }
+        // Write to a temporary file first so an interrupted run can't leave a truncated cache.
+        // Its name is unique to this save, as other runs may be saving the same cache.
+        static SAVES: AtomicUsize = AtomicUsize::new(0);
+        let save = SAVES.fetch_add(1, Ordering::Relaxed);
+        let tmp = self
+            .path
+            .with_extension(format!("{}.{}.tmp", std::process::id(), save));
+        let json = serde_json::to_vec(&self.data)?;
+        let written = fs::write(&tmp, json)
+            .with_context(|| format!("Failed to write cache: {}", tmp.display()))
+            .and_then(|()| {
+                fs::rename(&tmp, &self.path)
+                    .with_context(|| format!("Failed to write cache: {}", self.path.display()))
+            });
+        if written.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        written
+    }
+}
+
+/// `$XDG_CACHE_HOME/simple-wc/stats.json`, or `~/.cache/simple-wc/stats.json`. Entries are
+/// keyed by absolute path, so one cache serves every directory.
+pub fn default_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(dir.join("simple-wc").join(CACHE_FILE))
+}
+
+fn cache_key(file: &Path) -> PathBuf {
+    file.canonicalize().unwrap_or_else(|_| file.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::FileStats;
+
+    const STATS: Analysis = Analysis {
+        stats: FileStats {
+            words: 2,
+            lines: 1,
+            chars: 11,
+        },
+        code: None,
+        metrics: BTreeMap::new(),
+        normalized: None,
+        language: None,
+        entities: None,
+    };
+
+    #[test]
+    fn test_roundtrip_and_invalidation() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_path = dir.path().join(CACHE_FILE);
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "hello world")?;
+
+        let mut cache = Cache::load(&cache_path);
+        let fingerprint = Fingerprint::of(&file, false)?;
+        assert_eq!(cache.get(&file, &fingerprint, ""), None);
+        cache.insert(&file, fingerprint, "", STATS);
+        cache.save()?;
+
+        let cache = Cache::load(&cache_path);
+        assert_eq!(
+            cache.get(&file, &Fingerprint::of(&file, false)?, ""),
+            Some(STATS)
+        );
+
+        fs::write(&file, "hello world, again")?;
+        assert_eq!(cache.get(&file, &Fingerprint::of(&file, false)?, ""), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_creates_directory() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "hello world")?;
+        let cache_path = dir.path().join("simple-wc").join(CACHE_FILE);
+
+        let mut cache = Cache::load(&cache_path);
+        cache.insert(&file, Fingerprint::of(&file, false)?, "", STATS);
+        cache.save()?;
+        assert!(cache_path.is_file());
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_saves() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "hello world")?;
+        let cache_path = dir.path().join(CACHE_FILE);
+
+        let saves: Vec<_> = (0..8)
+            .map(|_| {
+                let mut cache = Cache::load(&cache_path);
+                cache.insert(&file, Fingerprint::of(&file, false)?, "", STATS);
+                Ok(std::thread::spawn(move || cache.save()))
+            })
+            .collect::<Result<_>>()?;
+        for save in saves {
+            save.join().expect("save doesn't panic")?;
+        }
+
+        // Only the cache is left, and it is whole.
+        let names: Vec<_> = fs::read_dir(dir.path())?
+            .map(|entry| Ok(entry?.file_name()))
+            .collect::<io::Result<_>>()?;
+        assert_eq!(names.len(), 2);
+        let fingerprint = Fingerprint::of(&file, false)?;
+        assert_eq!(
+            Cache::load(&cache_path).get(&file, &fingerprint, ""),
+            Some(STATS)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_required_when_verifying() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "hello world")?;
+
+        let mut cache = Cache::load(&dir.path().join(CACHE_FILE));
+        cache.insert(&file, Fingerprint::of(&file, false)?, "", STATS);
+        assert_eq!(cache.get(&file, &Fingerprint::of(&file, true)?, ""), None);
+
+        cache.insert(&file, Fingerprint::of(&file, true)?, "", STATS);
+        assert_eq!(
+            cache.get(&file, &Fingerprint::of(&file, true)?, ""),
+            Some(STATS)
+        );
+        assert_eq!(
+            cache.get(&file, &Fingerprint::of(&file, false)?, ""),
+            Some(STATS)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_settings_must_match() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("a.md");
+        fs::write(&file, "hello world")?;
+
+        let mut cache = Cache::load(&dir.path().join(CACHE_FILE));
+        cache.insert(&file, Fingerprint::of(&file, false)?, "", STATS);
+        let fingerprint = Fingerprint::of(&file, false)?;
+        assert_eq!(cache.get(&file, &fingerprint, "markup=markdown"), None);
+        assert_eq!(cache.get(&file, &fingerprint, ""), Some(STATS));
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_removes_missing_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "hello world")?;
+
+        let mut cache = Cache::load(&dir.path().join(CACHE_FILE));
+        cache.insert(&file, Fingerprint::of(&file, false)?, "", STATS);
+        assert_eq!(cache.prune(), 0);
+
+        fs::remove_file(&file)?;
+        assert_eq!(cache.prune(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_cache_is_ignored() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache_path = dir.path().join(CACHE_FILE);
+        fs::write(&cache_path, "not json")?;
+
+        let cache = Cache::load(&cache_path);
+        assert!(cache.data.entries.is_empty());
+        Ok(())
+    }
+}
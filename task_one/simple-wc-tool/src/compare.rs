@@ -0,0 +1,328 @@
+use crate::analyzer::FileStats;
+use crate::capitalize;
+use crate::limits::{self, Violation};
+use crate::report::{format_stats, Report};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+type Metrics = BTreeMap<String, usize>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+/// Signed difference between two sets of counts.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct StatsDelta {
+    pub words: i64,
+    pub lines: i64,
+    pub chars: i64,
+    /// Differences of the additional metrics, such as `tokens`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: BTreeMap<String, i64>,
+}
+
+impl StatsDelta {
+    pub fn between(before: Option<&FileStats>, after: Option<&FileStats>) -> Self {
+        let before = before.copied().unwrap_or_default();
+        let after = after.copied().unwrap_or_default();
+        StatsDelta {
+            words: after.words as i64 - before.words as i64,
+            lines: after.lines as i64 - before.lines as i64,
+            chars: after.chars as i64 - before.chars as i64,
+            metrics: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the differences of the metrics in `names`, missing ones counting as 0.
+    fn with_metrics(
+        mut self,
+        names: &[&str],
+        before: Option<&Metrics>,
+        after: Option<&Metrics>,
+    ) -> Self {
+        let value = |metrics: Option<&Metrics>, name: &str| {
+            metrics
+                .and_then(|m| m.get(name))
+                .copied()
+                .unwrap_or_default() as i64
+        };
+        self.metrics = names
+            .iter()
+            .map(|&name| (name.to_string(), value(after, name) - value(before, name)))
+            .collect();
+        self
+    }
+
+    fn is_zero(&self) -> bool {
+        self.words == 0
+            && self.lines == 0
+            && self.chars == 0
+            && self.metrics.values().all(|&delta| delta == 0)
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FileDelta {
+    pub path: String,
+    pub status: Status,
+    pub before: Option<FileStats>,
+    pub after: Option<FileStats>,
+    /// Values of the compared metrics, from the current run unless the file was removed.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: Metrics,
+    pub delta: StatsDelta,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TotalDelta {
+    pub before: FileStats,
+    pub after: FileStats,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: Metrics,
+    pub delta: StatsDelta,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Comparison {
+    pub files: Vec<FileDelta>,
+    pub total: TotalDelta,
+    /// Limits the current run goes over.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<Violation>,
+}
+
+/// Compares the current run against a baseline, matching files by path. Additional
+/// metrics are compared when both runs have them, e.g. both counted tokens.
+pub fn compare(baseline: &Report, current: &Report) -> Comparison {
+    let names: Vec<&str> = current
+        .metrics
+        .keys()
+        .filter(|name| baseline.metrics.contains_key(*name))
+        .map(String::as_str)
+        .collect();
+    let compared = |metrics: &Metrics| -> Metrics {
+        names
+            .iter()
+            .map(|&name| {
+                (
+                    name.to_string(),
+                    metrics.get(name).copied().unwrap_or_default(),
+                )
+            })
+            .collect()
+    };
+
+    type Counts<'a> = (&'a FileStats, &'a Metrics);
+    let mut paths: BTreeMap<&str, (Option<Counts>, Option<Counts>)> = BTreeMap::new();
+    for file in &baseline.files {
+        paths.entry(&file.path).or_default().0 = Some((&file.stats, &file.metrics));
+    }
+    for file in &current.files {
+        paths.entry(&file.path).or_default().1 = Some((&file.stats, &file.metrics));
+    }
+
+    let files =
+        paths
+            .into_iter()
+            .map(|(path, (before, after))| {
+                let delta = StatsDelta::between(before.map(|b| b.0), after.map(|a| a.0))
+                    .with_metrics(&names, before.map(|b| b.1), after.map(|a| a.1));
+                let status = match (before, after) {
+                    (None, _) => Status::Added,
+                    (_, None) => Status::Removed,
+                    _ if delta.is_zero() => Status::Unchanged,
+                    _ => Status::Changed,
+                };
+                FileDelta {
+                    path: path.to_string(),
+                    status,
+                    before: before.map(|b| *b.0),
+                    after: after.map(|a| *a.0),
+                    metrics: compared(after.or(before).map_or(&Metrics::new(), |c| c.1)),
+                    delta,
+                }
+            })
+            .collect();
+
+    Comparison {
+        files,
+        total: TotalDelta {
+            before: baseline.total,
+            after: current.total,
+            metrics: compared(&current.metrics),
+            delta: StatsDelta::between(Some(&baseline.total), Some(&current.total)).with_metrics(
+                &names,
+                Some(&baseline.metrics),
+                Some(&current.metrics),
+            ),
+        },
+        violations: current.violations.clone(),
+    }
+}
+
+/// The compared metrics, like [`format_stats`], e.g. `  Tokens: 120 (+8)`.
+fn format_metrics(metrics: &Metrics, delta: Option<&StatsDelta>) -> String {
+    metrics
+        .iter()
+        .map(
+            |(name, value)| match delta.and_then(|d| d.metrics.get(name)) {
+                Some(change) => format!("  {}: {} ({:+})", capitalize(name), value, change),
+                None => format!("  {}: {}", capitalize(name), value),
+            },
+        )
+        .collect()
+}
+
+pub fn print_comparison(comparison: &Comparison) {
+    for (status, title) in [
+        (Status::Changed, "Changed"),
+        (Status::Added, "Added"),
+        (Status::Removed, "Removed"),
+    ] {
+        let files: Vec<_> = comparison
+            .files
+            .iter()
+            .filter(|file| file.status == status)
+            .collect();
+        if files.is_empty() {
+            continue;
+        }
+
+        println!("{}:", title);
+        for file in files {
+            let stats = match (file.before, file.after) {
+                (Some(before), Some(after)) => {
+                    format_stats(&after, Some(&before))
+                        + &format_metrics(&file.metrics, Some(&file.delta))
+                }
+                (before, after) => {
+                    format_stats(&after.or(before).unwrap_or_default(), None)
+                        + &format_metrics(&file.metrics, None)
+                }
+            };
+            println!("  {}  {}", file.path, stats);
+        }
+    }
+
+    let unchanged = comparison
+        .files
+        .iter()
+        .filter(|file| file.status == Status::Unchanged)
+        .count();
+    println!("Unchanged files: {}", unchanged);
+    let total = &comparison.total;
+    println!(
+        "Total: {}{}",
+        format_stats(&total.after, Some(&total.before)),
+        format_metrics(&total.metrics, Some(&total.delta))
+    );
+    limits::print_violations(&comparison.violations);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::FileReport;
+
+    fn file(path: &str, words: usize, lines: usize, chars: usize) -> FileReport {
+        FileReport {
+            path: path.to_string(),
+            stats: FileStats {
+                words,
+                lines,
+                chars,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let baseline = Report::new(vec![
+            file("a.txt", 10, 2, 50),
+            file("b.txt", 5, 1, 20),
+            file("c.txt", 1, 1, 3),
+        ]);
+        let current = Report::new(vec![
+            file("a.txt", 12, 3, 61),
+            file("c.txt", 1, 1, 3),
+            file("d.txt", 4, 1, 15),
+        ]);
+
+        let comparison = compare(&baseline, &current);
+        let statuses: Vec<_> = comparison
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("a.txt", Status::Changed),
+                ("b.txt", Status::Removed),
+                ("c.txt", Status::Unchanged),
+                ("d.txt", Status::Added),
+            ]
+        );
+
+        assert_eq!(
+            comparison.files[0].delta,
+            StatsDelta {
+                words: 2,
+                lines: 1,
+                chars: 11,
+                metrics: BTreeMap::new(),
+            }
+        );
+        assert_eq!(
+            comparison.files[1].delta,
+            StatsDelta {
+                words: -5,
+                lines: -1,
+                chars: -20,
+                metrics: BTreeMap::new(),
+            }
+        );
+        assert_eq!(
+            comparison.total.delta,
+            StatsDelta {
+                words: 1,
+                lines: 1,
+                chars: 6,
+                metrics: BTreeMap::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_compare_metrics() {
+        let with_tokens = |path: &str, tokens: usize| FileReport {
+            metrics: BTreeMap::from([("tokens".to_string(), tokens)]),
+            ..file(path, 10, 2, 50)
+        };
+        let baseline = Report::new(vec![with_tokens("a.txt", 12), with_tokens("b.txt", 3)]);
+        let current = Report::new(vec![with_tokens("a.txt", 15), with_tokens("b.txt", 3)]);
+
+        let comparison = compare(&baseline, &current);
+        let a = &comparison.files[0];
+        assert_eq!(a.status, Status::Changed);
+        assert_eq!(a.delta.metrics["tokens"], 3);
+        assert_eq!(a.metrics["tokens"], 15);
+        assert_eq!(comparison.files[1].status, Status::Unchanged);
+        assert_eq!(comparison.total.delta.metrics["tokens"], 3);
+
+        // A baseline without tokens has nothing to compare them to.
+        let baseline = Report::new(vec![file("a.txt", 10, 2, 50), file("b.txt", 10, 2, 50)]);
+        let comparison = compare(&baseline, &current);
+        assert!(comparison
+            .files
+            .iter()
+            .all(|f| f.status == Status::Unchanged && f.delta.metrics.is_empty()));
+    }
+}
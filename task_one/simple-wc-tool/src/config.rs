@@ -0,0 +1,524 @@
+use crate::delimited::DelimitedMode;
+use crate::group::{GroupBy, SortKey};
+use crate::markup::MarkupMode;
+use crate::report::Column;
+use crate::{interval_from_secs, parse_size, Args, Format};
+use anyhow::{bail, Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command, ValueEnum};
+use serde::Deserialize;
+use simple_wc_tool::normalization::Normalization;
+use simple_wc_tool::timeline::{parse_duration, TimestampFormat};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Project config, looked up in the current directory and its ancestors.
+pub const PROJECT_CONFIG: &str = ".simple-wc.toml";
+
+/// Option defaults, as found in `[defaults]` or in a `[profiles.NAME]` table.
+///
+/// Keys are the long option names, e.g. `dup-stats = true` or `limits = ["*.rs:lines<=1000"]`.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    pub recursive: Option<bool>,
+    pub dup_stats: Option<bool>,
+    pub top_lines: Option<usize>,
+    pub normalize_lines: Option<Vec<String>>,
+    pub approx: Option<bool>,
+    pub interval: Option<f64>,
+    pub no_cache: Option<bool>,
+    pub cache_hash: Option<bool>,
+    pub format: Option<String>,
+    pub limits: Option<Vec<String>>,
+    pub group_by: Option<String>,
+    pub tree: Option<bool>,
+    pub sort: Option<String>,
+    pub top: Option<usize>,
+    pub markup: Option<String>,
+    pub delimited: Option<String>,
+    pub no_header: Option<bool>,
+    pub tokens: Option<PathBuf>,
+    pub normalize: Option<String>,
+    pub metrics: Option<Vec<String>>,
+    pub sample_budget: Option<String>,
+    pub detect_language: Option<bool>,
+    pub entities: Option<bool>,
+    pub top_entities: Option<usize>,
+    pub timestamps: Option<String>,
+    pub bucket: Option<String>,
+}
+
+impl Settings {
+    /// Returns these settings with every value set in `other` taking priority.
+    fn overlay(self, other: Settings) -> Settings {
+        Settings {
+            recursive: other.recursive.or(self.recursive),
+            dup_stats: other.dup_stats.or(self.dup_stats),
+            top_lines: other.top_lines.or(self.top_lines),
+            normalize_lines: other.normalize_lines.or(self.normalize_lines),
+            approx: other.approx.or(self.approx),
+            interval: other.interval.or(self.interval),
+            no_cache: other.no_cache.or(self.no_cache),
+            cache_hash: other.cache_hash.or(self.cache_hash),
+            format: other.format.or(self.format),
+            limits: other.limits.or(self.limits),
+            group_by: other.group_by.or(self.group_by),
+            tree: other.tree.or(self.tree),
+            sort: other.sort.or(self.sort),
+            top: other.top.or(self.top),
+            markup: other.markup.or(self.markup),
+            delimited: other.delimited.or(self.delimited),
+            no_header: other.no_header.or(self.no_header),
+            tokens: other.tokens.or(self.tokens),
+            normalize: other.normalize.or(self.normalize),
+            metrics: other.metrics.or(self.metrics),
+            sample_budget: other.sample_budget.or(self.sample_budget),
+            detect_language: other.detect_language.or(self.detect_language),
+            entities: other.entities.or(self.entities),
+            top_entities: other.top_entities.or(self.top_entities),
+            timestamps: other.timestamps.or(self.timestamps),
+            bucket: other.bucket.or(self.bucket),
+        }
+    }
+
+    /// Fills in every option of `command` that wasn't given on the command line.
+    pub fn apply(self, args: &mut Args, command: &Command, matches: &ArgMatches) -> Result<()> {
+        // A subcommand has only some of the options. Keys for the others are left out, so
+        // that e.g. `limits` doesn't make `freq` fail.
+        let declared = |id: &str| command.get_arguments().any(|arg| arg.get_id() == id);
+        let on_command_line =
+            |id: &str| declared(id) && matches.value_source(id) == Some(ValueSource::CommandLine);
+        // `--no-dup-stats` keeps `dup-stats` off, and `--cache` keeps `no-cache` off.
+        let negation = |id: &str| match id.strip_prefix("no_") {
+            Some(rest) => rest.to_string(),
+            None => format!("no_{}", id),
+        };
+        let applies =
+            |id: &str| declared(id) && !on_command_line(id) && !on_command_line(&negation(id));
+        let mut applied = Vec::new();
+
+        macro_rules! set {
+            ($field:ident, $value:expr) => {
+                set!(args.$field, $field, $value)
+            };
+            ($target:expr, $id:ident, $value:expr) => {
+                if applies(stringify!($id)) {
+                    $target = $value;
+                    applied.push(stringify!($id));
+                }
+            };
+        }
+
+        // Switches are off by default, so only turning one on changes anything.
+        if self.recursive == Some(true) {
+            set!(args.input.recursive, recursive, true);
+        }
+        if self.dup_stats == Some(true) {
+            set!(dup_stats, true);
+        }
+        if let Some(value) = self.top_lines {
+            set!(top_lines, value);
+        }
+        if let Some(values) = self.normalize_lines {
+            let parsed = values
+                .iter()
+                .map(|v| v.parse())
+                .collect::<Result<Vec<_>, String>>()
+                .map_err(anyhow::Error::msg)
+                .context("Invalid normalize-lines in config")?;
+            set!(normalize_lines, parsed);
+        }
+        if self.approx == Some(true) {
+            set!(approx, true);
+        }
+        if let Some(value) = self.interval {
+            let parsed = interval_from_secs(value)
+                .map_err(anyhow::Error::msg)
+                .context("Invalid interval in config")?;
+            set!(interval, parsed);
+        }
+        if self.no_cache == Some(true) {
+            set!(args.config.no_cache, no_cache, true);
+        }
+        if self.cache_hash == Some(true) {
+            set!(cache_hash, true);
+        }
+        if let Some(value) = self.format {
+            let parsed = Format::from_str(&value, true)
+                .map_err(anyhow::Error::msg)
+                .context("Invalid format in config")?;
+            set!(format, parsed);
+        }
+        if let Some(values) = self.limits {
+            let parsed = values
+                .iter()
+                .map(|v| v.parse())
+                .collect::<Result<Vec<_>, String>>()
+                .map_err(anyhow::Error::msg)
+                .context("Invalid limits in config")?;
+            set!(limit, parsed);
+        }
+        if let Some(value) = self.group_by {
+            let parsed = GroupBy::from_str(&value, true)
+                .map_err(anyhow::Error::msg)
+                .context("Invalid group-by in config")?;
+            set!(group_by, Some(parsed));
+        }
+        if self.tree == Some(true) {
+            set!(tree, true);
+        }
+        if let Some(value) = self.sort {
+            let parsed = SortKey::from_str(&value, true)
+                .map_err(anyhow::Error::msg)
+                .context("Invalid sort in config")?;
+            set!(sort, Some(parsed));
+        }
+        if let Some(value) = self.top {
+            set!(top, Some(value));
+        }
+        if let Some(value) = self.markup {
+            let parsed = MarkupMode::from_str(&value, true)
+                .map_err(anyhow::Error::msg)
+                .context("Invalid markup in config")?;
+            set!(markup, Some(parsed));
+        }
+        if let Some(value) = self.delimited {
+            let parsed = DelimitedMode::from_str(&value, true)
+                .map_err(anyhow::Error::msg)
+                .context("Invalid delimited in config")?;
+            set!(delimited, Some(parsed));
+        }
+        if self.no_header == Some(true) {
+            set!(no_header, true);
+        }
+        if let Some(path) = self.tokens {
+            set!(tokens, Some(path));
+        }
+        if let Some(value) = self.normalize {
+            let parsed = Normalization::from_str(&value, true)
+                .map_err(anyhow::Error::msg)
+                .context("Invalid normalize in config")?;
+            set!(normalize, Some(parsed));
+        }
+        if let Some(values) = self.metrics {
+            let parsed = values
+                .iter()
+                .map(|v| Column::from_str(v, true))
+                .collect::<Result<Vec<_>, String>>()
+                .map_err(anyhow::Error::msg)
+                .context("Invalid metrics in config")?;
+            set!(metrics, parsed);
+        }
+        if let Some(value) = self.sample_budget {
+            let parsed = parse_size(&value)
+                .map_err(anyhow::Error::msg)
+                .context("Invalid sample-budget in config")?;
+            set!(sample_budget, parsed);
+        }
+        if self.detect_language == Some(true) {
+            set!(detect_language, true);
+        }
+        if self.entities == Some(true) {
+            set!(entities, true);
+        }
+        if let Some(value) = self.top_entities {
+            set!(top_entities, value);
+        }
+        if let Some(value) = self.timestamps {
+            let parsed = TimestampFormat::from_str(&value, true)
+                .map_err(anyhow::Error::msg)
+                .context("Invalid timestamps in config")?;
+            set!(timestamps, Some(parsed));
+        }
+        if let Some(value) = self.bucket {
+            let parsed = parse_duration(&value)
+                .map_err(anyhow::Error::msg)
+                .context("Invalid bucket in config")?;
+            set!(bucket, parsed);
+        }
+
+        // Values from the config skip clap's checks, so conflicts between them and with the
+        // options on the command line are looked for here.
+        let find = |id: &str| command.get_arguments().find(|arg| arg.get_id() == id);
+        let conflicts = |a: &clap::Arg, b: &clap::Arg| {
+            let with = |a, b: &clap::Arg| {
+                command
+                    .get_arg_conflicts_with(a)
+                    .iter()
+                    .any(|arg| arg.get_id() == b.get_id())
+            };
+            with(a, b) || with(b, a)
+        };
+        for (i, &id) in applied.iter().enumerate() {
+            let arg = find(id).expect("applied keys are declared");
+            for &other_id in &applied[i + 1..] {
+                if conflicts(arg, find(other_id).expect("applied keys are declared")) {
+                    bail!(
+                        "Config keys `{}` and `{}` can't be used together",
+                        id.replace('_', "-"),
+                        other_id.replace('_', "-")
+                    );
+                }
+            }
+            let given = command
+                .get_arguments()
+                .filter(|other| on_command_line(other.get_id().as_str()));
+            for other in given {
+                if conflicts(arg, other) {
+                    bail!(
+                        "Config key `{}` can't be used with --{} (--no-config ignores the config)",
+                        id.replace('_', "-"),
+                        other.get_long().unwrap_or(other.get_id().as_str())
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub defaults: Settings,
+    pub profiles: BTreeMap<String, Settings>,
+}
+
+impl ConfigFile {
+    fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config: {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("Failed to parse config: {}", path.display()))
+    }
+
+    /// Merges two config files, with values from `other` taking priority.
+    fn overlay(mut self, other: ConfigFile) -> ConfigFile {
+        self.defaults = self.defaults.overlay(other.defaults);
+        for (name, profile) in other.profiles {
+            let base = self.profiles.remove(&name).unwrap_or_default();
+            self.profiles.insert(name, base.overlay(profile));
+        }
+        self
+    }
+
+    /// Settings to apply: the defaults, overridden by the selected profile.
+    pub fn resolve(self, profile: Option<&str>) -> Result<Settings> {
+        let mut profiles = self.profiles;
+        match profile {
+            None => Ok(self.defaults),
+            Some(name) => match profiles.remove(name) {
+                Some(profile) => Ok(self.defaults.overlay(profile)),
+                None => bail!("Unknown profile: {}", name),
+            },
+        }
+    }
+}
+
+/// Loads the user config and the nearest project config, the latter taking priority.
+pub fn load() -> Result<ConfigFile> {
+    let cwd = std::env::current_dir()?;
+    let paths = [user_config_path(), find_project_config(&cwd)];
+
+    let mut config = ConfigFile::default();
+    for path in paths.into_iter().flatten() {
+        config = config.overlay(ConfigFile::load(&path)?);
+    }
+    Ok(config)
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join("simple-wc").join("config.toml")).filter(|path| path.is_file())
+}
+
+fn find_project_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cli, Command};
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn parse(toml: &str) -> ConfigFile {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn args_with(settings: Settings, cli: &[&str]) -> Result<Args> {
+        let command = Cli::command();
+        let matches = command.clone().get_matches_from(cli);
+        let mut args = Cli::from_arg_matches(&matches)?.count;
+        settings.apply(&mut args, &command, &matches)?;
+        Ok(args)
+    }
+
+    #[test]
+    fn test_profile_overrides_defaults() -> Result<()> {
+        let config = parse(
+            r#"
+            [defaults]
+            top-lines = 5
+            format = "json"
+
+            [profiles.logs]
+            dup-stats = true
+            top-lines = 20
+            "#,
+        );
+
+        let settings = config.resolve(Some("logs"))?;
+        assert_eq!(settings.top_lines, Some(20));
+        assert_eq!(settings.dup_stats, Some(true));
+        assert_eq!(settings.format.as_deref(), Some("json"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_profile_and_key() {
+        assert!(parse("").resolve(Some("docs")).is_err());
+        assert!(toml::from_str::<ConfigFile>("[defaults]\ncolour = true").is_err());
+    }
+
+    #[test]
+    fn test_project_config_overrides_user_config() -> Result<()> {
+        let user =
+            parse("[defaults]\ntop-lines = 5\nrecursive = true\n[profiles.docs]\napprox = true");
+        let project = parse("[defaults]\ntop-lines = 8\n[profiles.docs]\ndup-stats = true");
+
+        let settings = user.overlay(project).resolve(Some("docs"))?;
+        assert_eq!(settings.top_lines, Some(8));
+        assert_eq!(settings.recursive, Some(true));
+        assert_eq!(settings.approx, Some(true));
+        assert_eq!(settings.dup_stats, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_line_wins() -> Result<()> {
+        let settings = Settings {
+            top_lines: Some(5),
+            dup_stats: Some(true),
+            limits: Some(vec!["*.rs:lines<=1000".to_string()]),
+            ..Default::default()
+        };
+
+        let args = args_with(
+            settings,
+            &["simple-wc-tool", "--top-lines", "3", "file.txt"],
+        )?;
+        assert_eq!(args.top_lines, 3);
+        assert!(args.dup_stats);
+        assert_eq!(args.limit.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_switches_can_be_turned_off() -> Result<()> {
+        let settings = Settings {
+            dup_stats: Some(true),
+            no_cache: Some(true),
+            entities: Some(true),
+            ..Default::default()
+        };
+
+        let args = args_with(
+            settings,
+            &["simple-wc-tool", "--no-dup-stats", "--cache", "file.txt"],
+        )?;
+        assert!(!args.dup_stats);
+        assert!(!args.config.no_cache);
+        assert!(args.entities);
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflicts_are_reported() {
+        let settings = || Settings {
+            dup_stats: Some(true),
+            ..Default::default()
+        };
+        assert!(args_with(settings(), &["simple-wc-tool", "--follow", "file.txt"]).is_err());
+        assert!(args_with(settings(), &["simple-wc-tool", "--estimate", "file.txt"]).is_err());
+
+        let settings = Settings {
+            no_cache: Some(true),
+            cache_hash: Some(true),
+            ..Default::default()
+        };
+        assert!(args_with(settings, &["simple-wc-tool", "file.txt"]).is_err());
+
+        // Switched off in the config, it doesn't conflict.
+        let settings = Settings {
+            dup_stats: Some(false),
+            ..Default::default()
+        };
+        assert!(args_with(settings, &["simple-wc-tool", "--follow", "file.txt"]).is_ok());
+    }
+
+    #[test]
+    fn test_subcommand_options() -> Result<()> {
+        let settings = Settings {
+            top: Some(5),
+            recursive: Some(true),
+            dup_stats: Some(true),
+            ..Default::default()
+        };
+
+        let matches =
+            Cli::command().get_matches_from(["simple-wc-tool", "report", "--top", "3", "dir"]);
+        let Some(Command::Report(report)) = Cli::from_arg_matches(&matches)?.command else {
+            panic!("expected the report subcommand");
+        };
+        let mut args = report.into_args();
+        let (_, matches) = matches.subcommand().expect("subcommand matches");
+        let command = Cli::command();
+        let command = command
+            .find_subcommand("report")
+            .expect("report subcommand");
+        settings.apply(&mut args, command, matches)?;
+        assert_eq!(args.top, Some(3));
+        assert!(args.input.recursive);
+        // Options the subcommand doesn't have are left alone.
+        assert!(!args.dup_stats);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_values_are_reported() {
+        let settings = Settings {
+            limits: Some(vec!["lines".to_string()]),
+            ..Default::default()
+        };
+        assert!(args_with(settings, &["simple-wc-tool", "file.txt"]).is_err());
+
+        for interval in [-1.0, 0.0, f64::NAN, f64::INFINITY] {
+            let settings = Settings {
+                interval: Some(interval),
+                ..Default::default()
+            };
+            assert!(args_with(settings, &["simple-wc-tool", "file.txt"]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_find_project_config() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let nested = dir.path().join("a").join("b");
+        fs::create_dir_all(&nested)?;
+        fs::write(dir.path().join(PROJECT_CONFIG), "")?;
+
+        assert_eq!(
+            find_project_config(&nested),
+            Some(dir.path().join(PROJECT_CONFIG))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+use crate::hll::HyperLogLog;
+use crate::region::{self, Region};
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// A transformation applied to every line before comparing it with others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineNormalization {
+    /// Strip leading and trailing whitespace.
+    Trim,
+    /// Compare lines case-insensitively.
+    Case,
+    /// Replace every run of digits with `#`.
+    Digits,
+    /// Replace timestamps (ISO 8601, syslog, clock times) with `<ts>`.
+    Timestamps,
+}
+
+impl FromStr for LineNormalization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trim" => Ok(LineNormalization::Trim),
+            "case" => Ok(LineNormalization::Case),
+            "digits" => Ok(LineNormalization::Digits),
+            "timestamps" => Ok(LineNormalization::Timestamps),
+            _ => Err(format!(
+                "unknown normalization '{}' (expected trim, case, digits or timestamps)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DupOptions {
+    pub normalize: Vec<LineNormalization>,
+    /// How many of the most repeated lines to report.
+    pub top: usize,
+    /// Estimate the distinct count with HyperLogLog instead of keeping every line.
+    pub approximate: bool,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct DupStats {
+    pub total: usize,
+    /// Number of different lines. Estimated when `approximate` is set.
+    pub distinct: usize,
+    /// Lines that occur exactly once. Not available in approximate mode.
+    pub unique: Option<usize>,
+    /// Different lines that occur more than once. Not available in approximate mode.
+    pub duplicated: Option<usize>,
+    /// Most repeated lines, most frequent first. Empty in approximate mode.
+    pub top: Vec<(String, usize)>,
+    pub approximate: bool,
+}
+
+/// Collects duplicate line statistics one line at a time.
+pub struct DupCounter {
+    options: DupOptions,
+    total: usize,
+    exact: HashMap<String, usize>,
+    approx: HyperLogLog,
+}
+
+impl DupCounter {
+    pub fn new(options: DupOptions) -> Self {
+        DupCounter {
+            options,
+            total: 0,
+            exact: HashMap::new(),
+            approx: HyperLogLog::new(),
+        }
+    }
+
+    pub fn add_line(&mut self, line: &str) {
+        self.total += 1;
+        let line = normalize_line(line, &self.options.normalize);
+
+        if self.options.approximate {
+            self.approx.insert(line.as_str());
+        } else {
+            *self.exact.entry(line).or_insert(0) += 1;
+        }
+    }
+
+    pub fn finish(self) -> DupStats {
+        if self.options.approximate {
+            return DupStats {
+                total: self.total,
+                distinct: self.approx.estimate().min(self.total),
+                unique: None,
+                duplicated: None,
+                top: Vec::new(),
+                approximate: true,
+            };
+        }
+
+        let unique = self.exact.values().filter(|&&count| count == 1).count();
+        let mut repeated: Vec<(String, usize)> = self
+            .exact
+            .iter()
+            .filter(|(_, &count)| count > 1)
+            .map(|(line, &count)| (line.clone(), count))
+            .collect();
+        let duplicated = repeated.len();
+
+        // Ties are broken alphabetically so the output is stable between runs.
+        repeated.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        repeated.truncate(self.options.top);
+
+        DupStats {
+            total: self.total,
+            distinct: self.exact.len(),
+            unique: Some(unique),
+            duplicated: Some(duplicated),
+            top: repeated,
+            approximate: false,
+        }
+    }
+}
+
+/// Reads files line by line and collects duplicate line statistics over all of them.
+pub fn analyze_files(
+    paths: &[PathBuf],
+    options: &DupOptions,
+    region: Option<&Region>,
+) -> Result<DupStats> {
+    let mut counter = DupCounter::new(options.clone());
+
+    for path in paths {
+        let file = region::open(path, region)?;
+        for line in BufReader::new(file).lines() {
+            let line =
+                line.with_context(|| format!("Failed to read line from file: {}", path.display()))?;
+            counter.add_line(&line);
+        }
+    }
+
+    Ok(counter.finish())
+}
+
+fn normalize_line(line: &str, normalize: &[LineNormalization]) -> String {
+    let has = |n| normalize.contains(&n);
+    let mut line = if has(LineNormalization::Trim) {
+        line.trim().to_string()
+    } else {
+        line.to_string()
+    };
+
+    // Timestamps go first, otherwise digit masking would destroy them.
+    if has(LineNormalization::Timestamps) {
+        line = timestamp_regex().replace_all(&line, "<ts>").into_owned();
+    }
+    if has(LineNormalization::Digits) {
+        line = digits_regex().replace_all(&line, "#").into_owned();
+    }
+    if has(LineNormalization::Case) {
+        line = line.to_lowercase();
+    }
+
+    line
+}
+
+fn timestamp_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            // ISO 8601 / RFC 3339: 2024-01-31T12:00:00.123+02:00
+            r"\d{4}-\d{2}-\d{2}(?:[T ]\d{2}:\d{2}(?::\d{2}(?:[.,]\d+)?)?(?:Z|[+-]\d{2}:?\d{2})?)?",
+            // Syslog: Jan  5 12:00:00
+            r"|(?:Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec) +\d{1,2} \d{2}:\d{2}:\d{2}",
+            // Common log format: 10/Oct/2000:13:55:36 -0700
+            r"|\d{2}/\w{3}/\d{4}:\d{2}:\d{2}:\d{2}(?: [+-]\d{4})?",
+            // Bare clock time: 12:00:00.123
+            r"|\d{2}:\d{2}:\d{2}(?:[.,]\d+)?",
+        ))
+        .expect("timestamp pattern is valid")
+    })
+}
+
+fn digits_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\d+").expect("digits pattern is valid"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(lines: &[&str], options: DupOptions) -> DupStats {
+        let mut counter = DupCounter::new(options);
+        for line in lines {
+            counter.add_line(line);
+        }
+        counter.finish()
+    }
+
+    #[test]
+    fn test_exact_counts() {
+        let stats = count(
+            &["a", "b", "a", "c", "a", "b"],
+            DupOptions {
+                top: 10,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            stats,
+            DupStats {
+                total: 6,
+                distinct: 3,
+                unique: Some(1),
+                duplicated: Some(2),
+                top: vec![("a".to_string(), 3), ("b".to_string(), 2)],
+                approximate: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_top_is_truncated() {
+        let stats = count(
+            &["a", "a", "b", "b", "c", "c"],
+            DupOptions {
+                top: 1,
+                ..Default::default()
+            },
+        );
+        assert_eq!(stats.top, vec![("a".to_string(), 2)]);
+        assert_eq!(stats.duplicated, Some(3));
+    }
+
+    #[test]
+    fn test_normalization() {
+        let options = DupOptions {
+            normalize: vec![
+                LineNormalization::Trim,
+                LineNormalization::Case,
+                LineNormalization::Timestamps,
+                LineNormalization::Digits,
+            ],
+            top: 10,
+            approximate: false,
+        };
+        let stats = count(
+            &[
+                "2024-01-31T12:00:00Z ERROR request 17 failed",
+                "  2024-02-01T08:30:12.5+02:00 error request 923 failed",
+                "Jan  5 12:00:00 ERROR request 4 failed",
+            ],
+            options,
+        );
+
+        assert_eq!(stats.distinct, 1);
+        assert_eq!(
+            stats.top,
+            vec![("<ts> error request # failed".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn test_approximate_mode() {
+        let stats = count(
+            &["a", "b", "a"],
+            DupOptions {
+                approximate: true,
+                top: 10,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.distinct, 2);
+        assert_eq!(stats.unique, None);
+        assert!(stats.top.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_normalization() {
+        assert!("upper".parse::<LineNormalization>().is_err());
+        assert_eq!(
+            "digits".parse::<LineNormalization>(),
+            Ok(LineNormalization::Digits)
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use crate::hll::HyperLogLog;
+use crate::region::{self, Region};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// How many inconsistent record numbers are listed; the rest are only counted.
+const MAX_LISTED_RECORDS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DelimitedMode {
+    /// Comma-separated values
+    Csv,
+    /// Tab-separated values
+    Tsv,
+    /// Pick by file extension; other files get no structural stats
+    Auto,
+}
+
+impl DelimitedMode {
+    /// The field delimiter for one file, or `None` when it isn't a delimited file.
+    pub fn delimiter_for(self, path: &Path) -> Option<u8> {
+        match self {
+            DelimitedMode::Csv => Some(b','),
+            DelimitedMode::Tsv => Some(b'\t'),
+            DelimitedMode::Auto => {
+                let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+                match ext.as_str() {
+                    "csv" => Some(b','),
+                    "tsv" | "tab" => Some(b'\t'),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnStats {
+    pub name: String,
+    pub empty: usize,
+    /// Estimated with HyperLogLog, so it stays cheap on large exports.
+    pub distinct: usize,
+    /// Longest value, in characters.
+    pub max_width: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableStats {
+    /// Data records, not counting the header.
+    pub records: usize,
+    /// Field count of the header, or of the first record without a header.
+    pub fields: usize,
+    pub min_fields: usize,
+    pub max_fields: usize,
+    /// Records whose field count differs from `fields`.
+    pub inconsistent: usize,
+    /// Numbers (1-based, header excluded) of the first inconsistent records.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inconsistent_records: Vec<usize>,
+    pub columns: Vec<ColumnStats>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    /// At the start of a field.
+    Start,
+    Unquoted,
+    Quoted,
+    /// Just saw a `"` inside a quoted field: either an escaped quote or the closing one.
+    QuoteInQuoted,
+}
+
+/// Splits input into records, following RFC 4180: fields may be quoted, and quoted
+/// fields may contain delimiters, `""` escapes and newlines. Blank lines are skipped.
+pub struct RecordParser {
+    delimiter: u8,
+    state: State,
+    field: Vec<u8>,
+    record: Vec<Vec<u8>>,
+}
+
+impl RecordParser {
+    pub fn new(delimiter: u8) -> Self {
+        RecordParser {
+            delimiter,
+            state: State::Start,
+            field: Vec::new(),
+            record: Vec::new(),
+        }
+    }
+
+    /// Feeds a chunk, calling `on_record` for every record completed in it.
+    pub fn update(&mut self, chunk: &[u8], mut on_record: impl FnMut(&[Vec<u8>])) {
+        for &byte in chunk {
+            match (self.state, byte) {
+                (State::Quoted, b'"') => self.state = State::QuoteInQuoted,
+                (State::Quoted, _) => self.field.push(byte),
+                (State::QuoteInQuoted, b'"') => {
+                    self.field.push(b'"');
+                    self.state = State::Quoted;
+                }
+                (State::Start, b'"') => self.state = State::Quoted,
+                (_, b'\n') => {
+                    if self.state == State::Unquoted && self.field.last() == Some(&b'\r') {
+                        self.field.pop();
+                    }
+                    self.end_record(&mut on_record);
+                }
+                (_, b) if b == self.delimiter => self.end_field(),
+                // A `\r` after a closing quote belongs to a `\r\n` line ending.
+                (State::QuoteInQuoted, b'\r') => {}
+                (_, _) => {
+                    self.field.push(byte);
+                    self.state = State::Unquoted;
+                }
+            }
+        }
+    }
+
+    /// Completes the last record when the input doesn't end with a newline.
+    pub fn finish(&mut self, mut on_record: impl FnMut(&[Vec<u8>])) {
+        if self.state != State::Start || !self.record.is_empty() {
+            self.end_record(&mut on_record);
+        }
+    }
+
+    fn end_field(&mut self) {
+        self.record.push(std::mem::take(&mut self.field));
+        self.state = State::Start;
+    }
+
+    fn end_record(&mut self, on_record: &mut impl FnMut(&[Vec<u8>])) {
+        let blank = self.record.is_empty() && self.state == State::Start;
+        self.end_field();
+        if !blank {
+            on_record(&self.record);
+        }
+        self.record.clear();
+    }
+}
+
+struct Column {
+    name: Option<String>,
+    empty: usize,
+    distinct: HyperLogLog,
+    max_width: usize,
+}
+
+/// Accumulates structural stats record by record.
+pub struct TableCounter {
+    header: bool,
+    columns: Vec<Column>,
+    records: usize,
+    /// Field count every record is expected to have.
+    fields: Option<usize>,
+    min_fields: usize,
+    max_fields: usize,
+    inconsistent: usize,
+    inconsistent_records: Vec<usize>,
+}
+
+impl TableCounter {
+    /// With `header`, the first record names the columns and isn't counted.
+    pub fn new(header: bool) -> Self {
+        TableCounter {
+            header,
+            columns: Vec::new(),
+            records: 0,
+            fields: None,
+            min_fields: usize::MAX,
+            max_fields: 0,
+            inconsistent: 0,
+            inconsistent_records: Vec::new(),
+        }
+    }
+
+    pub fn add_record(&mut self, record: &[Vec<u8>]) {
+        if self.header && self.fields.is_none() {
+            self.fields = Some(record.len());
+            for field in record {
+                self.columns.push(Column::new(Some(
+                    String::from_utf8_lossy(field).trim().to_string(),
+                )));
+            }
+            return;
+        }
+
+        self.records += 1;
+        self.min_fields = self.min_fields.min(record.len());
+        self.max_fields = self.max_fields.max(record.len());
+        if *self.fields.get_or_insert(record.len()) != record.len() {
+            self.inconsistent += 1;
+            if self.inconsistent_records.len() < MAX_LISTED_RECORDS {
+                self.inconsistent_records.push(self.records);
+            }
+        }
+
+        while self.columns.len() < record.len() {
+            self.columns.push(Column::new(None));
+        }
+        for (column, field) in self.columns.iter_mut().zip(record) {
+            let value = String::from_utf8_lossy(field);
+            if value.trim().is_empty() {
+                column.empty += 1;
+            }
+            column.distinct.insert(value.as_ref());
+            column.max_width = column.max_width.max(value.chars().count());
+        }
+    }
+
+    pub fn finish(self) -> TableStats {
+        let fields = self.fields.unwrap_or_default();
+        TableStats {
+            records: self.records,
+            fields,
+            min_fields: if self.records == 0 {
+                fields
+            } else {
+                self.min_fields
+            },
+            max_fields: if self.records == 0 {
+                fields
+            } else {
+                self.max_fields
+            },
+            inconsistent: self.inconsistent,
+            inconsistent_records: self.inconsistent_records,
+            columns: self
+                .columns
+                .into_iter()
+                .enumerate()
+                .map(|(i, column)| column.finish(i))
+                .collect(),
+        }
+    }
+}
+
+impl Column {
+    fn new(name: Option<String>) -> Self {
+        Column {
+            name,
+            empty: 0,
+            distinct: HyperLogLog::new(),
+            max_width: 0,
+        }
+    }
+
+    fn finish(self, index: usize) -> ColumnStats {
+        ColumnStats {
+            name: self
+                .name
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| format!("column {}", index + 1)),
+            empty: self.empty,
+            distinct: self.distinct.estimate(),
+            max_width: self.max_width,
+        }
+    }
+}
+
+/// Computes structural stats of a delimited file.
+pub fn analyze_file(
+    path: &Path,
+    delimiter: u8,
+    header: bool,
+    region: Option<&Region>,
+) -> Result<TableStats> {
+    let file = region::open(path, region)?;
+    analyze_reader(file, delimiter, header)
+        .with_context(|| format!("Failed to read file: {}", path.display()))
+}
+
+pub fn analyze_reader<R: Read>(reader: R, delimiter: u8, header: bool) -> Result<TableStats> {
+    let mut reader = BufReader::new(reader);
+    let mut parser = RecordParser::new(delimiter);
+    let mut counter = TableCounter::new(header);
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        parser.update(chunk, |record| counter.add_record(record));
+        let len = chunk.len();
+        reader.consume(len);
+    }
+    parser.finish(|record| counter.add_record(record));
+    Ok(counter.finish())
+}
+
+pub fn print_table_stats(stats: &TableStats) {
+    println!("Records: {}", stats.records);
+    if stats.min_fields == stats.max_fields {
+        println!("Fields per record: {}", stats.fields);
+    } else {
+        println!(
+            "Fields per record: {} (min {}, max {})",
+            stats.fields, stats.min_fields, stats.max_fields
+        );
+    }
+    if stats.inconsistent > 0 {
+        let listed: Vec<String> = stats
+            .inconsistent_records
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        let more = if stats.inconsistent > listed.len() {
+            ", ..."
+        } else {
+            ""
+        };
+        println!(
+            "Inconsistent records: {} (records {}{})",
+            stats.inconsistent,
+            listed.join(", "),
+            more
+        );
+    }
+    if !stats.columns.is_empty() {
+        println!(
+            "{:<24} {:>10} {:>12} {:>10}",
+            "Column", "Empty", "Distinct~", "Max width"
+        );
+        for column in &stats.columns {
+            println!(
+                "{:<24} {:>10} {:>12} {:>10}",
+                column.name, column.empty, column.distinct, column.max_width
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(input: &str, delimiter: u8) -> Vec<Vec<String>> {
+        let mut found = Vec::new();
+        let mut parser = RecordParser::new(delimiter);
+        let mut collect = |record: &[Vec<u8>]| {
+            found.push(
+                record
+                    .iter()
+                    .map(|f| String::from_utf8_lossy(f).into_owned())
+                    .collect(),
+            )
+        };
+        // Feed one byte at a time to exercise state kept between chunks.
+        for byte in input.as_bytes() {
+            parser.update(std::slice::from_ref(byte), &mut collect);
+        }
+        parser.finish(&mut collect);
+        found
+    }
+
+    #[test]
+    fn test_quoted_fields() {
+        let input = "a,\"b,c\",\"say \"\"hi\"\"\"\r\n\"multi\nline\",,x\n\n";
+        assert_eq!(
+            records(input, b','),
+            vec![vec!["a", "b,c", "say \"hi\""], vec!["multi\nline", "", "x"],]
+        );
+        assert_eq!(
+            records("a\tb\nc\td", b'\t'),
+            vec![vec!["a", "b"], vec!["c", "d"]]
+        );
+    }
+
+    #[test]
+    fn test_table_stats() -> Result<()> {
+        let input = "name,city\nann,Oslo\nbob,\n\"carl\njr\",Oslo\ndan\n";
+        let stats = analyze_reader(input.as_bytes(), b',', true)?;
+
+        assert_eq!(stats.records, 4);
+        assert_eq!(stats.fields, 2);
+        assert_eq!((stats.min_fields, stats.max_fields), (1, 2));
+        assert_eq!(stats.inconsistent, 1);
+        assert_eq!(stats.inconsistent_records, vec![4]);
+
+        let city = &stats.columns[1];
+        assert_eq!(city.name, "city");
+        assert_eq!((city.empty, city.distinct, city.max_width), (1, 2, 4));
+        assert_eq!(stats.columns[0].max_width, 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_without_header_uses_first_record() -> Result<()> {
+        let stats = analyze_reader("1,2,3\n4,5\n6,7,8\n".as_bytes(), b',', false)?;
+        assert_eq!(stats.records, 3);
+        assert_eq!(stats.fields, 3);
+        assert_eq!(stats.inconsistent_records, vec![2]);
+        assert_eq!(stats.columns[2].name, "column 3");
+        Ok(())
+    }
+}
@@ -0,0 +1,311 @@
+//! Counting entities of social-media text: emoji, URLs, email addresses, hashtags,
+//! mentions and numbers, along with their most common values.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::sync::OnceLock;
+
+/// Most common values of each kind kept in an [`Entities`]. Totals over several inputs
+/// merge these, so their top values are exact as long as no input has more distinct ones.
+pub const KEPT_VALUES: usize = 100;
+
+/// Distinct values tracked per kind while counting; later new values are still counted
+/// but can't make it into the top values.
+const MAX_DISTINCT: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Emoji,
+    Urls,
+    Emails,
+    Hashtags,
+    Mentions,
+    Numbers,
+}
+
+impl EntityKind {
+    pub const ALL: [EntityKind; 6] = [
+        EntityKind::Emoji,
+        EntityKind::Urls,
+        EntityKind::Emails,
+        EntityKind::Hashtags,
+        EntityKind::Mentions,
+        EntityKind::Numbers,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            EntityKind::Emoji => "Emoji",
+            EntityKind::Urls => "URLs",
+            EntityKind::Emails => "Emails",
+            EntityKind::Hashtags => "Hashtags",
+            EntityKind::Mentions => "Mentions",
+            EntityKind::Numbers => "Numbers",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntityCount {
+    pub count: usize,
+    /// Most common values with their counts, most common first.
+    pub top: Vec<(String, usize)>,
+}
+
+/// Entity counts by kind. Adding two merges them as if counted over both inputs.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Entities(pub BTreeMap<EntityKind, EntityCount>);
+
+impl std::ops::Add for Entities {
+    type Output = Entities;
+
+    fn add(mut self, other: Entities) -> Entities {
+        for (kind, other) in other.0 {
+            let entry = self.0.entry(kind).or_default();
+            let mut values: HashMap<String, usize> =
+                std::mem::take(&mut entry.top).into_iter().collect();
+            for (value, count) in other.top {
+                *values.entry(value).or_default() += count;
+            }
+            entry.count += other.count;
+            entry.top = top_values(values, KEPT_VALUES);
+        }
+        self
+    }
+}
+
+/// Values sorted by count, most common first, ties in alphabetical order.
+fn top_values(values: HashMap<String, usize>, n: usize) -> Vec<(String, usize)> {
+    let mut values: Vec<_> = values.into_iter().collect();
+    values.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    values.truncate(n);
+    values
+}
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("valid entity pattern"))
+}
+
+fn url_regex() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    regex(&CELL, r#"(?i)\b(?:https?://|www\.)[^\s<>"]+"#)
+}
+
+fn email_regex() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    regex(&CELL, r"[\w.+-]+@[\w-]+(?:\.[\w-]+)*\.\p{L}{2,}")
+}
+
+/// Hashtags and mentions, with the sigil in group 1 and the name in group 2.
+fn tag_regex() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    regex(&CELL, r"(?:^|[^\w&#@/])([#@])(\w+)")
+}
+
+fn number_regex() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    regex(&CELL, r"^[-+]?[0-9]+(?:[.,][0-9]+)*%?$")
+}
+
+/// An emoji with its modifiers, or a whole ZWJ sequence, flag or keycap. Characters
+/// that are only emoji with U+FE0F, like ©, count when they have it.
+fn emoji_regex() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    regex(
+        &CELL,
+        r"\p{Regional_Indicator}{2}|[#*0-9]\x{FE0F}?\x{20E3}|(?:\p{Emoji_Presentation}|\p{Extended_Pictographic}\x{FE0F})[\x{1F3FB}-\x{1F3FF}]?[\x{E0020}-\x{E007F}]*(?:\x{200D}(?:\p{Emoji_Presentation}|\p{Extended_Pictographic}\x{FE0F}?)[\x{1F3FB}-\x{1F3FF}]?)*",
+    )
+}
+
+/// Trailing punctuation that ends a sentence rather than the URL. A closing
+/// parenthesis stays if the URL opened one, as in Wikipedia links.
+fn trim_url(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"']);
+        let trimmed = match trimmed.strip_suffix(')') {
+            Some(rest) if trimmed.matches('(').count() < trimmed.matches(')').count() => rest,
+            _ => trimmed,
+        };
+        if trimmed.len() == url.len() {
+            return url;
+        }
+        url = trimmed;
+    }
+}
+
+/// Finds the entities of a line. URLs are found first, so that the address of a
+/// `mailto:` link or the digits of a URL aren't counted again, then emails.
+fn find_entities(line: &str, mut found: impl FnMut(EntityKind, &str)) {
+    let mut claimed: Vec<Range<usize>> = Vec::new();
+    let overlaps = |claimed: &[Range<usize>], r: &Range<usize>| {
+        claimed.iter().any(|c| c.start < r.end && r.start < c.end)
+    };
+
+    for m in url_regex().find_iter(line) {
+        let url = trim_url(m.as_str());
+        found(EntityKind::Urls, url);
+        claimed.push(m.start()..m.start() + url.len());
+    }
+    for m in email_regex().find_iter(line) {
+        if !overlaps(&claimed, &m.range()) {
+            found(EntityKind::Emails, &m.as_str().to_lowercase());
+            claimed.push(m.range());
+        }
+    }
+    for caps in tag_regex().captures_iter(line) {
+        let (sigil, name) = (&caps[1], caps.get(2).expect("tag name"));
+        let range = name.start() - 1..name.end();
+        if overlaps(&claimed, &range) {
+            continue;
+        }
+        let kind = match sigil {
+            "#" if name.as_str().chars().all(|c| c.is_ascii_digit()) => continue,
+            "#" => EntityKind::Hashtags,
+            _ => EntityKind::Mentions,
+        };
+        found(kind, &format!("{}{}", sigil, name.as_str().to_lowercase()));
+        claimed.push(range);
+    }
+    for m in emoji_regex().find_iter(line) {
+        found(EntityKind::Emoji, m.as_str());
+    }
+
+    let mut start = 0;
+    for token in line.split_whitespace() {
+        let offset = start + line[start..].find(token).unwrap_or(0);
+        start = offset + token.len();
+        if overlaps(&claimed, &(offset..start)) {
+            continue;
+        }
+        let number = token
+            .trim_start_matches(['(', '"', '\'', '$', '€', '£'])
+            .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '"', '\'']);
+        if number_regex().is_match(number) {
+            found(EntityKind::Numbers, number);
+        }
+    }
+}
+
+/// Counts entities in text fed line by line.
+#[derive(Debug, Default, Clone)]
+pub struct EntityCounter {
+    counts: BTreeMap<EntityKind, usize>,
+    values: BTreeMap<EntityKind, HashMap<String, usize>>,
+}
+
+impl EntityCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn line(&mut self, line: &str) {
+        let Self { counts, values } = self;
+        find_entities(line, |kind, value| {
+            *counts.entry(kind).or_default() += 1;
+            let values = values.entry(kind).or_default();
+            if let Some(count) = values.get_mut(value) {
+                *count += 1;
+            } else if values.len() < MAX_DISTINCT {
+                values.insert(value.to_string(), 1);
+            }
+        });
+    }
+
+    /// Counts of every kind, including those that didn't occur.
+    pub fn entities(&self) -> Entities {
+        let entities = EntityKind::ALL
+            .iter()
+            .map(|&kind| {
+                let values = self.values.get(&kind).cloned().unwrap_or_default();
+                let count = EntityCount {
+                    count: self.counts.get(&kind).copied().unwrap_or(0),
+                    top: top_values(values, KEPT_VALUES),
+                };
+                (kind, count)
+            })
+            .collect();
+        Entities(entities)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(line: &str) -> Vec<(EntityKind, String)> {
+        let mut entities = Vec::new();
+        find_entities(line, |kind, value| entities.push((kind, value.to_string())));
+        entities
+    }
+
+    #[test]
+    fn test_finds_entities() {
+        use EntityKind::*;
+        let entity = |kind, value: &str| (kind, value.to_string());
+        assert_eq!(
+            find("Loved it 😂👍🏽 see https://example.com/a_(b)?x=1. Mail Me@Example.org!"),
+            vec![
+                entity(Urls, "https://example.com/a_(b)?x=1"),
+                entity(Emails, "me@example.org"),
+                entity(Emoji, "😂"),
+                entity(Emoji, "👍🏽"),
+            ]
+        );
+        assert_eq!(
+            find("#RustLang and @ferris, not #1 or a&#39; (www.rust-lang.org)"),
+            vec![
+                entity(Urls, "www.rust-lang.org"),
+                entity(Hashtags, "#rustlang"),
+                entity(Mentions, "@ferris"),
+            ]
+        );
+        assert_eq!(
+            find("👨‍👩‍👧 🇩🇪 3️⃣ ©️ but not © or ™"),
+            vec![
+                entity(Emoji, "👨\u{200d}👩\u{200d}👧"),
+                entity(Emoji, "🇩🇪"),
+                entity(Emoji, "3\u{fe0f}\u{20e3}"),
+                entity(Emoji, "©\u{fe0f}"),
+            ]
+        );
+        assert_eq!(
+            find("Paid $1,250.50 (-3%) in 2024, not v2 or 3rd."),
+            vec![
+                entity(Numbers, "1,250.50"),
+                entity(Numbers, "-3%"),
+                entity(Numbers, "2024"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_counts_and_merges() {
+        let mut a = EntityCounter::new();
+        a.line("#Rust #rust #go 🦀");
+        let mut b = EntityCounter::new();
+        b.line("#go #go #go 42");
+
+        let a = a.entities();
+        assert_eq!(a.0.len(), EntityKind::ALL.len());
+        assert_eq!(a.0[&EntityKind::Urls], EntityCount::default());
+        assert_eq!(
+            a.0[&EntityKind::Hashtags].top,
+            vec![("#rust".to_string(), 2), ("#go".to_string(), 1)]
+        );
+
+        let total = a + b.entities();
+        let hashtags = &total.0[&EntityKind::Hashtags];
+        assert_eq!(hashtags.count, 6);
+        assert_eq!(
+            hashtags.top,
+            vec![("#go".to_string(), 4), ("#rust".to_string(), 2)]
+        );
+        assert_eq!(total.0[&EntityKind::Emoji].count, 1);
+        assert_eq!(total.0[&EntityKind::Numbers].count, 1);
+    }
+}
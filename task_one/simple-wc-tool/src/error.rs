@@ -0,0 +1,100 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Why an input couldn't be analyzed. The path is missing when the input wasn't a file.
+#[derive(Debug, thiserror::Error)]
+pub enum AnalyzerError {
+    #[error("{}: file not found", .path.display())]
+    NotFound { path: PathBuf },
+
+    #[error("{}: permission denied", .path.display())]
+    PermissionDenied { path: PathBuf },
+
+    /// `offset` is the position of the first invalid byte in the input.
+    #[error("{}invalid UTF-8 at byte {offset}", prefix(.path))]
+    InvalidUtf8 { path: Option<PathBuf>, offset: u64 },
+
+    #[error("{}{source}", prefix(.path))]
+    Io {
+        path: Option<PathBuf>,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl AnalyzerError {
+    /// Classifies an I/O error by its kind.
+    pub fn io(path: Option<&Path>, source: io::Error) -> Self {
+        match (source.kind(), path) {
+            (io::ErrorKind::NotFound, Some(path)) => AnalyzerError::NotFound {
+                path: path.to_path_buf(),
+            },
+            (io::ErrorKind::PermissionDenied, Some(path)) => AnalyzerError::PermissionDenied {
+                path: path.to_path_buf(),
+            },
+            _ => AnalyzerError::Io {
+                path: path.map(Path::to_path_buf),
+                source,
+            },
+        }
+    }
+
+    /// Attaches the path of the file the error happened in, unless it has one.
+    pub fn with_path(self, file: &Path) -> Self {
+        match self {
+            AnalyzerError::InvalidUtf8 { path: None, offset } => AnalyzerError::InvalidUtf8 {
+                path: Some(file.to_path_buf()),
+                offset,
+            },
+            AnalyzerError::Io { path: None, source } => AnalyzerError::io(Some(file), source),
+            other => other,
+        }
+    }
+
+    /// The file the error happened in, if known.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            AnalyzerError::NotFound { path } | AnalyzerError::PermissionDenied { path } => {
+                Some(path)
+            }
+            AnalyzerError::InvalidUtf8 { path, .. } | AnalyzerError::Io { path, .. } => {
+                path.as_deref()
+            }
+        }
+    }
+}
+
+fn prefix(path: &Option<PathBuf>) -> String {
+    match path {
+        Some(path) => format!("{}: ", path.display()),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_kinds() {
+        let path = Path::new("notes.txt");
+        let err = AnalyzerError::io(Some(path), io::ErrorKind::NotFound.into());
+        assert!(matches!(err, AnalyzerError::NotFound { .. }));
+        assert_eq!(err.to_string(), "notes.txt: file not found");
+
+        let err = AnalyzerError::io(None, io::ErrorKind::PermissionDenied.into());
+        assert!(matches!(err, AnalyzerError::Io { path: None, .. }));
+        let err = err.with_path(path);
+        assert!(matches!(err, AnalyzerError::PermissionDenied { .. }));
+
+        let err = AnalyzerError::InvalidUtf8 {
+            path: None,
+            offset: 7,
+        };
+        assert_eq!(err.to_string(), "invalid UTF-8 at byte 7");
+        assert_eq!(
+            err.with_path(path).to_string(),
+            "notes.txt: invalid UTF-8 at byte 7"
+        );
+    }
+}
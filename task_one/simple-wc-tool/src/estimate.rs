@@ -0,0 +1,318 @@
+//! Estimating the counts of files too large to read in full, from random samples.
+//!
+//! The file is cut into equal strata and one block is read at a random offset in each.
+//! Every count is a sum over character positions (a word is counted where it starts, a
+//! line where its newline is), so the counts of a block extrapolate linearly to the file.
+
+use crate::analyzer::{self, FileStats};
+use crate::error::AnalyzerError;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+type Result<T> = std::result::Result<T, AnalyzerError>;
+
+/// z-score of a two-sided 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// Longest UTF-8 sequence, read around each block to see its neighboring characters.
+const CONTEXT: u64 = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct EstimateOptions {
+    /// Bytes to read at most. Files no larger than this are counted exactly.
+    pub budget: u64,
+    /// Size of each sampled block.
+    pub block_size: u64,
+}
+
+impl Default for EstimateOptions {
+    fn default() -> Self {
+        EstimateOptions {
+            budget: 8 << 20,
+            block_size: 64 << 10,
+        }
+    }
+}
+
+/// An estimated count with the half-width of its 95% confidence interval.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Interval {
+    pub value: usize,
+    pub margin: usize,
+}
+
+impl Interval {
+    pub fn low(&self) -> usize {
+        self.value.saturating_sub(self.margin)
+    }
+
+    pub fn high(&self) -> usize {
+        self.value + self.margin
+    }
+}
+
+impl std::ops::Add for Interval {
+    type Output = Interval;
+
+    /// Samples of different files are independent, so margins add in quadrature.
+    fn add(self, other: Interval) -> Interval {
+        let margin = (self.margin as f64).hypot(other.margin as f64);
+        Interval {
+            value: self.value + other.value,
+            margin: margin.round() as usize,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Estimate {
+    /// Whether the whole input was read, so that the margins are zero.
+    pub exact: bool,
+    pub size: u64,
+    pub sampled_bytes: u64,
+    pub words: Interval,
+    pub lines: Interval,
+    pub chars: Interval,
+}
+
+impl Estimate {
+    pub fn stats(&self) -> FileStats {
+        FileStats {
+            words: self.words.value,
+            lines: self.lines.value,
+            chars: self.chars.value,
+        }
+    }
+}
+
+impl std::ops::Add for Estimate {
+    type Output = Estimate;
+
+    fn add(self, other: Estimate) -> Estimate {
+        Estimate {
+            exact: self.exact && other.exact,
+            size: self.size + other.size,
+            sampled_bytes: self.sampled_bytes + other.sampled_bytes,
+            words: self.words + other.words,
+            lines: self.lines + other.lines,
+            chars: self.chars + other.chars,
+        }
+    }
+}
+
+/// Estimates the counts of a file, reading at most `options.budget` bytes.
+pub fn estimate_file(path: &Path, options: &EstimateOptions) -> Result<Estimate> {
+    let io = |e| AnalyzerError::io(Some(path), e);
+    let mut file = File::open(path).map_err(io)?;
+    let size = file.metadata().map_err(io)?.len();
+
+    if size <= options.budget {
+        let stats = analyzer::analyze_file(path)?;
+        let exact = |value| Interval { value, margin: 0 };
+        return Ok(Estimate {
+            exact: true,
+            size,
+            sampled_bytes: size,
+            words: exact(stats.words),
+            lines: exact(stats.lines),
+            chars: exact(stats.chars),
+        });
+    }
+
+    let block = options.block_size.clamp(1, options.budget.max(1));
+    let blocks = (options.budget / block).max(1);
+    let stratum = size / blocks;
+    let mut rng = SplitMix64(size ^ 0x5DEE_CE66_D1CE_4E5B);
+
+    let mut samples = Vec::new();
+    for i in 0..blocks {
+        let offset = i * stratum + rng.below(stratum - block + 1);
+        samples.push(sample(&mut file, offset, block, size).map_err(io)?);
+    }
+
+    // The last line and the final newline are counted like the analyzer does.
+    let mut last = [0];
+    file.seek(SeekFrom::Start(size - 1)).map_err(io)?;
+    file.read_exact(&mut last).map_err(io)?;
+    let terminated = last[0] == b'\n';
+
+    let sampled = blocks * block;
+    let extrapolate = |count: fn(&Counts) -> usize, adjust: f64| {
+        let densities: Vec<f64> = samples
+            .iter()
+            .map(|s| count(s) as f64 / block as f64)
+            .collect();
+        let n = densities.len() as f64;
+        let mean = densities.iter().sum::<f64>() / n;
+        let variance = match densities.len() {
+            1 => 0.0,
+            _ => densities.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / (n - 1.0),
+        };
+        let correction = 1.0 - sampled as f64 / size as f64;
+        let margin = Z_95 * size as f64 * (variance / n * correction).sqrt();
+        Interval {
+            value: (mean * size as f64 + adjust).max(0.0).round() as usize,
+            margin: margin.round() as usize,
+        }
+    };
+    let unterminated = if terminated { 0.0 } else { 1.0 };
+    Ok(Estimate {
+        exact: false,
+        size,
+        sampled_bytes: sampled,
+        words: extrapolate(|c| c.words, 0.0),
+        lines: extrapolate(|c| c.lines, unterminated),
+        chars: extrapolate(|c| c.chars, -1.0 + unterminated),
+    })
+}
+
+/// Counts attributed to the characters starting in one block.
+#[derive(Debug, Default, PartialEq)]
+struct Counts {
+    words: usize,
+    lines: usize,
+    chars: usize,
+}
+
+fn sample(file: &mut File, offset: u64, len: u64, size: u64) -> std::io::Result<Counts> {
+    let start = offset.saturating_sub(CONTEXT);
+    let end = (offset + len + CONTEXT).min(size);
+    let mut buf = vec![0; (end - start) as usize];
+    file.seek(SeekFrom::Start(start))?;
+    file.read_exact(&mut buf)?;
+    let from = (offset - start) as usize;
+    Ok(count_block(&buf, from, from + len as usize, start == 0))
+}
+
+/// Counts the characters of `buf` that start in `from..to`; the rest only serves as
+/// context. `at_start` says whether `buf` begins at the start of the input.
+fn count_block(buf: &[u8], from: usize, to: usize, at_start: bool) -> Counts {
+    // Each character with its position; invalid bytes count as one character each,
+    // which only matters for files the analyzer would reject.
+    let mut chars = Vec::new();
+    let mut pos = 0;
+    for chunk in buf.utf8_chunks() {
+        for (i, c) in chunk.valid().char_indices() {
+            chars.push((pos + i, c));
+        }
+        pos += chunk.valid().len();
+        if !chunk.invalid().is_empty() {
+            chars.push((pos, char::REPLACEMENT_CHARACTER));
+            pos += chunk.invalid().len();
+        }
+    }
+
+    let mut counts = Counts::default();
+    for (i, &(pos, c)) in chars.iter().enumerate() {
+        if pos < from || pos >= to {
+            continue;
+        }
+        let prev = match i {
+            0 if at_start && pos == 0 => None,
+            0 => continue,
+            _ => Some(chars[i - 1].1),
+        };
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        if !c.is_whitespace() && prev.is_none_or(char::is_whitespace) {
+            counts.words += 1;
+        }
+        if c == '\n' {
+            counts.lines += 1;
+        }
+        // `\r\n` is one separator.
+        if !(c == '\r' && next == Some('\n')) {
+            counts.chars += 1;
+        }
+    }
+    counts
+}
+
+/// A small, seedable generator, so that repeated runs on a file pick the same samples.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`, for `n > 0`.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_block_counts_add_up() {
+        let text = "héllo  wörld\r\nsecond line\n\nthird";
+        let bytes = text.as_bytes();
+        let whole = count_block(bytes, 0, bytes.len(), true);
+        let expected = analyzer::analyze_reader(bytes, analyzer::Analyzer::new())
+            .unwrap()
+            .stats;
+        assert_eq!(whole.words, expected.words);
+        // Unterminated last line, and no final newline to discount.
+        assert_eq!(whole.lines + 1, expected.lines);
+        assert_eq!(whole.chars, expected.chars);
+
+        // Splitting anywhere, even inside a character, gives the same totals.
+        for split in 0..bytes.len() {
+            let a = count_block(bytes, 0, split, true);
+            let b = count_block(bytes, split, bytes.len(), true);
+            assert_eq!(a.words + b.words, whole.words, "split at {}", split);
+            assert_eq!(a.chars + b.chars, whole.chars, "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn test_estimate_within_interval() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("big.txt");
+        let mut text = String::new();
+        for i in 0..40_000 {
+            text.push_str(&"word ".repeat(i % 7 + 1));
+            text.push_str("ünïcödé\n");
+        }
+        fs::write(&path, &text)?;
+        let exact = analyzer::analyze_file(&path)?;
+
+        let options = EstimateOptions {
+            budget: 64 << 10,
+            block_size: 4 << 10,
+        };
+        let estimate = estimate_file(&path, &options)?;
+        assert!(!estimate.exact);
+        assert_eq!(estimate.sampled_bytes, 64 << 10);
+        for (interval, actual) in [
+            (estimate.words, exact.words),
+            (estimate.lines, exact.lines),
+            (estimate.chars, exact.chars),
+        ] {
+            assert!(interval.margin > 0);
+            // Generous, so that the test doesn't fail 5% of the time.
+            let slack = interval.margin * 2;
+            assert!(
+                interval.value.abs_diff(actual) <= slack,
+                "{:?} vs {}",
+                interval,
+                actual
+            );
+        }
+        assert_eq!(estimate_file(&path, &options)?, estimate);
+
+        let small = estimate_file(&path, &EstimateOptions::default())?;
+        assert!(small.exact);
+        assert_eq!(small.stats(), exact);
+        Ok(())
+    }
+}
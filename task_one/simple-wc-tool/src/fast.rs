@@ -0,0 +1,286 @@
+//! Counting lines, characters and bytes without decoding lines, for when words and
+//! the other line-based metrics aren't needed. Gives the same numbers as the
+//! [`Analyzer`](crate::analyzer::Analyzer).
+
+use crate::analyzer::OnChunk;
+use crate::error::AnalyzerError;
+use crate::region::{self, Region};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::Read;
+use std::ops::ControlFlow;
+use std::path::Path;
+
+type Result<T> = std::result::Result<T, AnalyzerError>;
+
+/// Files at least this large are memory-mapped instead of read in chunks.
+const MMAP_THRESHOLD: u64 = 1 << 20;
+
+const BUFFER_SIZE: usize = 1 << 16;
+
+/// Memory-mapped files are counted in slices of this size, to report progress.
+const MAP_CHUNK_SIZE: usize = 16 << 20;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FastCounts {
+    pub lines: usize,
+    pub chars: usize,
+    pub bytes: usize,
+}
+
+/// Incremental counter over raw bytes. Newlines and `\r\n` pairs are found with
+/// vectorized search; characters are the bytes that don't continue a UTF-8 sequence.
+#[derive(Debug, Default)]
+pub struct FastCounter {
+    count_chars: bool,
+    newlines: usize,
+    crlf: usize,
+    /// Bytes that start a character.
+    starts: usize,
+    bytes: usize,
+    last: Option<u8>,
+    /// An incomplete UTF-8 sequence at the end of the previous chunk.
+    partial: Vec<u8>,
+}
+
+impl FastCounter {
+    /// With `count_chars`, characters are counted too. The input is always validated as
+    /// UTF-8, so that a file fails here as it would in the analyzer.
+    pub fn new(count_chars: bool) -> Self {
+        FastCounter {
+            count_chars,
+            ..Self::default()
+        }
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) -> Result<()> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        self.validate(chunk)?;
+        self.newlines += memchr::memchr_iter(b'\n', chunk).count();
+        if self.count_chars {
+            self.crlf += memchr::memmem::find_iter(chunk, b"\r\n").count();
+            if self.last == Some(b'\r') && chunk[0] == b'\n' {
+                self.crlf += 1;
+            }
+            self.starts += chunk.iter().filter(|&&b| b & 0xC0 != 0x80).count();
+        }
+        self.bytes += chunk.len();
+        self.last = chunk.last().copied();
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<FastCounts> {
+        if !self.partial.is_empty() {
+            return Err(invalid_utf8(self.bytes - self.partial.len()));
+        }
+        let terminated = self.last == Some(b'\n');
+        let lines = self.newlines + usize::from(self.last.is_some() && !terminated);
+        // The analyzer strips `\r` before `\n`, and a final newline isn't a separator.
+        let chars = if self.count_chars {
+            self.starts - self.crlf - usize::from(terminated)
+        } else {
+            0
+        };
+        Ok(FastCounts {
+            lines,
+            chars,
+            bytes: self.bytes,
+        })
+    }
+
+    /// Finishes input that was cut off before its end, leaving out a character that was
+    /// only partly read.
+    pub fn finish_partial(mut self) -> Result<FastCounts> {
+        if !self.partial.is_empty() {
+            self.partial.clear();
+            if self.count_chars {
+                self.starts -= 1;
+            }
+        }
+        self.finish()
+    }
+
+    fn validate(&mut self, mut chunk: &[u8]) -> Result<()> {
+        // Offset of `chunk` in the input.
+        let mut offset = self.bytes;
+        if !self.partial.is_empty() {
+            let needed = sequence_len(self.partial[0]) - self.partial.len();
+            let take = needed.min(chunk.len());
+            let start = self.bytes - self.partial.len();
+            self.partial.extend_from_slice(&chunk[..take]);
+            chunk = &chunk[take..];
+            offset += take;
+            if take < needed {
+                return Ok(());
+            }
+            std::str::from_utf8(&self.partial).map_err(|_| invalid_utf8(start))?;
+            self.partial.clear();
+        }
+
+        match std::str::from_utf8(chunk) {
+            Ok(_) => Ok(()),
+            Err(e) if e.error_len().is_none() => {
+                self.partial.extend_from_slice(&chunk[e.valid_up_to()..]);
+                Ok(())
+            }
+            Err(e) => Err(invalid_utf8(offset + e.valid_up_to())),
+        }
+    }
+}
+
+/// Length of the UTF-8 sequence started by `lead`.
+fn sequence_len(lead: u8) -> usize {
+    match lead {
+        0xF0..=0xFF => 4,
+        0xE0..=0xEF => 3,
+        0xC0..=0xDF => 2,
+        _ => 1,
+    }
+}
+
+fn invalid_utf8(offset: usize) -> AnalyzerError {
+    AnalyzerError::InvalidUtf8 {
+        path: None,
+        offset: offset as u64,
+    }
+}
+
+/// Counts a file, or its selected region. Large files are memory-mapped.
+pub fn count_file(path: &Path, count_chars: bool, region: Option<&Region>) -> Result<FastCounts> {
+    count_file_with_progress(
+        path,
+        count_chars,
+        region,
+        &mut |_| ControlFlow::Continue(()),
+    )
+}
+
+/// Like [`count_file`], reporting every chunk counted to `on_chunk`, which can stop
+/// counting early.
+pub fn count_file_with_progress(
+    path: &Path,
+    count_chars: bool,
+    region: Option<&Region>,
+    on_chunk: &mut OnChunk,
+) -> Result<FastCounts> {
+    let io = |e| AnalyzerError::io(Some(path), e);
+    let mut counter = FastCounter::new(count_chars);
+    let mut feed = |counter: &mut FastCounter, chunk: &[u8]| {
+        counter.update(chunk).map_err(|e| e.with_path(path))?;
+        Ok(on_chunk(chunk.len()).is_break())
+    };
+
+    if region.is_none() {
+        let file = File::open(path).map_err(io)?;
+        if file.metadata().map_err(io)?.len() >= MMAP_THRESHOLD {
+            // SAFETY: the map is only read while counting. If another process truncates
+            // the file meanwhile, reading may fault, as it would for any mmap-based tool.
+            let map = unsafe { Mmap::map(&file) }.map_err(io)?;
+            for chunk in map.chunks(MAP_CHUNK_SIZE) {
+                if feed(&mut counter, chunk)? {
+                    return counter.finish_partial();
+                }
+            }
+            return counter.finish().map_err(|e| e.with_path(path));
+        }
+    }
+
+    let mut reader = region::open(path, region)?;
+    let mut buffer = vec![0; BUFFER_SIZE];
+    loop {
+        let n = reader.read(&mut buffer).map_err(io)?;
+        if n == 0 {
+            break;
+        }
+        if feed(&mut counter, &buffer[..n])? {
+            return counter.finish_partial();
+        }
+    }
+    counter.finish().map_err(|e| e.with_path(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::Analyzer;
+
+    fn fast(input: &[u8], chunk_size: usize) -> Result<FastCounts> {
+        let mut counter = FastCounter::new(true);
+        for chunk in input.chunks(chunk_size) {
+            counter.update(chunk)?;
+        }
+        counter.finish()
+    }
+
+    #[test]
+    fn test_matches_analyzer() -> Result<()> {
+        let inputs = [
+            "",
+            "\n",
+            "\n\n\n",
+            "no newline",
+            "Hello, World!\r\nThis is a test.\r\n",
+            "ends with cr\r",
+            "Unicode: привет, мир! 你好, 世界! 🦀\nsecond\r\n\r\nlast",
+        ];
+        for input in inputs {
+            let mut analyzer = Analyzer::new();
+            analyzer.update(input.as_bytes())?;
+            let expected = analyzer.finish()?.stats;
+
+            for chunk_size in [1, 2, 3, 7, 4096] {
+                let counts = fast(input.as_bytes(), chunk_size)?;
+                assert_eq!(
+                    (counts.lines, counts.chars, counts.bytes),
+                    (expected.lines, expected.chars, input.len()),
+                    "{:?} in chunks of {}",
+                    input,
+                    chunk_size
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let offset = |result: Result<FastCounts>| match result {
+            Err(AnalyzerError::InvalidUtf8 { offset, .. }) => Some(offset),
+            _ => None,
+        };
+        assert_eq!(offset(fast(b"ok\n\xff\xfe\n", 3)), Some(3));
+        assert_eq!(offset(fast(b"a\xc3\xa9\xa9", 2)), Some(3));
+        // Cut off in the middle of a character.
+        assert_eq!(
+            offset(fast("aé".as_bytes().split_last().unwrap().1, 1)),
+            Some(1)
+        );
+
+        // Also when only lines are counted.
+        let mut counter = FastCounter::new(false);
+        assert!(counter.update(b"\xff\n").is_err());
+    }
+
+    #[test]
+    fn test_stops_reading() -> Result<()> {
+        let file = tempfile::NamedTempFile::new().map_err(|e| AnalyzerError::io(None, e))?;
+        std::fs::write(file.path(), "é\n".repeat(BUFFER_SIZE))
+            .map_err(|e| AnalyzerError::io(None, e))?;
+
+        let counts =
+            count_file_with_progress(file.path(), true, None, &mut |_| ControlFlow::Break(()))?;
+        // The buffer ends in the first byte of an "é", on a line of its own.
+        assert_eq!(counts.bytes, BUFFER_SIZE);
+        assert_eq!(counts.lines, BUFFER_SIZE / 3 + 1);
+        assert_eq!(counts.chars, BUFFER_SIZE / 3 * 2);
+
+        // Without characters, nothing was counted for the cut-off one.
+        let mut counter = FastCounter::new(false);
+        counter.update(b"a\n\xc3")?;
+        let counts = counter.finish_partial()?;
+        assert_eq!((counts.lines, counts.chars, counts.bytes), (2, 0, 3));
+        Ok(())
+    }
+}
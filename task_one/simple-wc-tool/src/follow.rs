@@ -0,0 +1,281 @@
+use crate::analyzer::{Analyzer, FileStats};
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the file is checked for new data between reports.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Bytes at the start of the file that are kept, to notice a file that was truncated
+/// and has grown past the old end again by the next poll.
+const HEAD_LEN: usize = 256;
+
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    None,
+    Appended,
+    /// The file got shorter than what was already read, or its start changed; counting
+    /// restarted.
+    Truncated,
+    /// Another file took the place of the followed one; counting restarted.
+    Rotated,
+}
+
+/// Keeps a file open and counts data as it is appended, like `tail -f`.
+pub struct Follower {
+    path: PathBuf,
+    file: File,
+    offset: u64,
+    /// The first bytes read, up to [`HEAD_LEN`].
+    head: Vec<u8>,
+    analyzer: Analyzer,
+}
+
+impl Follower {
+    /// Opens the file and counts its current content with `analyzer`.
+    pub fn open(path: &Path, analyzer: Analyzer) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+        let mut follower = Follower {
+            path: path.to_path_buf(),
+            file,
+            offset: 0,
+            head: Vec::new(),
+            analyzer,
+        };
+        follower.read_available()?;
+        Ok(follower)
+    }
+
+    pub fn stats(&self) -> FileStats {
+        self.analyzer.stats()
+    }
+
+    /// Reads whatever was appended since the last call.
+    pub fn poll(&mut self) -> Result<Change> {
+        if self.is_rotated() {
+            let file = File::open(&self.path)
+                .with_context(|| format!("Failed to reopen file: {}", self.path.display()))?;
+            self.file = file;
+            self.restart()?;
+            return Ok(Change::Rotated);
+        }
+
+        let len = self
+            .file
+            .metadata()
+            .with_context(|| format!("Failed to stat file: {}", self.path.display()))?
+            .len();
+        if len < self.offset || self.head_changed()? {
+            self.file.seek(SeekFrom::Start(0))?;
+            self.restart()?;
+            return Ok(Change::Truncated);
+        }
+
+        if self.read_available()? > 0 {
+            Ok(Change::Appended)
+        } else {
+            Ok(Change::None)
+        }
+    }
+
+    /// Whether the start of the file differs from what was read there.
+    fn head_changed(&mut self) -> Result<bool> {
+        let mut head = Vec::with_capacity(self.head.len());
+        self.file.seek(SeekFrom::Start(0))?;
+        (&mut self.file)
+            .take(self.head.len() as u64)
+            .read_to_end(&mut head)
+            .with_context(|| format!("Failed to read from file: {}", self.path.display()))?;
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        Ok(head != self.head)
+    }
+
+    fn restart(&mut self) -> Result<()> {
+        self.offset = 0;
+        self.head.clear();
+        self.analyzer.reset();
+        self.read_available()?;
+        Ok(())
+    }
+
+    fn read_available(&mut self) -> Result<usize> {
+        let mut buf = [0u8; 64 * 1024];
+        let mut total = 0;
+
+        loop {
+            let n = self
+                .file
+                .read(&mut buf)
+                .with_context(|| format!("Failed to read from file: {}", self.path.display()))?;
+            if n == 0 {
+                break;
+            }
+            self.analyzer.update(&buf[..n]).with_context(|| {
+                format!("Failed to read line from file: {}", self.path.display())
+            })?;
+            let missing = HEAD_LEN - self.head.len();
+            self.head.extend_from_slice(&buf[..n.min(missing)]);
+            self.offset += n as u64;
+            total += n;
+        }
+
+        Ok(total)
+    }
+
+    /// Whether the path now points to a different file than the open one.
+    /// A missing path is not a rotation yet: the new file may not be created yet.
+    #[cfg(unix)]
+    fn is_rotated(&self) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        match (std::fs::metadata(&self.path), self.file.metadata()) {
+            (Ok(current), Ok(open)) => current.ino() != open.ino() || current.dev() != open.dev(),
+            _ => false,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn is_rotated(&self) -> bool {
+        false
+    }
+}
+
+/// Follows a file until the process is interrupted, printing the counts
+/// whenever they changed, at most once per `interval`.
+pub fn run(path: &Path, analyzer: Analyzer, interval: Duration) -> Result<()> {
+    let mut follower = Follower::open(path, analyzer)?;
+
+    let mut last = follower.stats();
+    let mut last_report = Instant::now();
+    print_update(&last, None);
+    // The rate makes no sense across a restart, so the next report omits it.
+    let mut restarted = false;
+
+    loop {
+        thread::sleep(POLL_INTERVAL.min(interval));
+
+        match follower.poll()? {
+            Change::Truncated => {
+                eprintln!("{}: file truncated", path.display());
+                restarted = true;
+            }
+            Change::Rotated => {
+                eprintln!("{}: file replaced, following new file", path.display());
+                restarted = true;
+            }
+            Change::Appended | Change::None => {}
+        }
+
+        let elapsed = last_report.elapsed();
+        if elapsed < interval {
+            continue;
+        }
+
+        let stats = follower.stats();
+        if stats != last || restarted {
+            let added = stats.lines as i64 - last.lines as i64;
+            let rate = (!restarted).then(|| (added, added as f64 / elapsed.as_secs_f64()));
+            print_update(&stats, rate);
+        }
+        last = stats;
+        restarted = false;
+        last_report = Instant::now();
+    }
+}
+
+fn print_update(stats: &FileStats, rate: Option<(i64, f64)>) {
+    let counts = format!(
+        "Words: {}  Lines: {}  Characters: {}",
+        stats.words, stats.lines, stats.chars
+    );
+    match rate {
+        Some((added, per_sec)) => {
+            println!("{}  ({:+} lines, {:.1} lines/sec)", counts, added, per_sec)
+        }
+        None => println!("{}", counts),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn append(path: &Path, content: &str) -> Result<()> {
+        let mut file = OpenOptions::new().append(true).open(path)?;
+        write!(file, "{}", content)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_appended_data_is_counted() -> Result<()> {
+        let file = NamedTempFile::new()?;
+        append(file.path(), "hello world\n")?;
+
+        let mut follower = Follower::open(file.path(), Analyzer::new())?;
+        assert_eq!(follower.stats().lines, 1);
+        assert_eq!(follower.poll()?, Change::None);
+
+        append(file.path(), "rust is great\n")?;
+        assert_eq!(follower.poll()?, Change::Appended);
+        assert_eq!(
+            follower.stats(),
+            FileStats {
+                words: 5,
+                lines: 2,
+                chars: 25,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncation_restarts_counting() -> Result<()> {
+        let file = NamedTempFile::new()?;
+        append(file.path(), "one two three\nfour five\n")?;
+        let mut follower = Follower::open(file.path(), Analyzer::new())?;
+
+        fs::write(file.path(), "six\n")?;
+        assert_eq!(follower.poll()?, Change::Truncated);
+        assert_eq!(follower.stats().words, 1);
+
+        // Truncated, then longer than before by the next poll.
+        fs::write(file.path(), "seven eight nine ten eleven\n")?;
+        assert_eq!(follower.poll()?, Change::Truncated);
+        assert_eq!(follower.stats().words, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_counts_with_the_given_analyzer() -> Result<()> {
+        let file = NamedTempFile::new()?;
+        append(file.path(), "# Title\n\n```\nlet x = 1;\n```\n")?;
+
+        let analyzer = Analyzer::with_markup(Some(crate::markup::Markup::Markdown));
+        let follower = Follower::open(file.path(), analyzer)?;
+        assert_eq!(follower.stats().words, 1);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rotation_follows_new_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("app.log");
+        fs::write(&path, "old line\n")?;
+        let mut follower = Follower::open(&path, Analyzer::new())?;
+
+        fs::rename(&path, dir.path().join("app.log.1"))?;
+        fs::write(&path, "new file line\n")?;
+
+        assert_eq!(follower.poll()?, Change::Rotated);
+        assert_eq!(follower.stats().words, 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+//! Word frequencies over any number of inputs.
+
+use crate::error::AnalyzerError;
+use crate::region::{self, Region};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct FreqOptions {
+    /// How many of the most frequent words to report.
+    pub top: usize,
+    /// Words with fewer characters are left out.
+    pub min_length: usize,
+    /// Count `Word` and `word` apart instead of lowercasing both.
+    pub case_sensitive: bool,
+}
+
+impl Default for FreqOptions {
+    fn default() -> Self {
+        FreqOptions {
+            top: 20,
+            min_length: 1,
+            case_sensitive: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Frequencies {
+    /// Words counted, after leaving out the short ones.
+    pub words: usize,
+    pub distinct: usize,
+    /// Most frequent words, most frequent first.
+    pub top: Vec<(String, usize)>,
+}
+
+/// Counts words one line at a time. Words are split at whitespace like the analyzer
+/// does, then stripped of the punctuation around them, so `word,` and `"word` are `word`.
+pub struct FreqCounter {
+    options: FreqOptions,
+    words: usize,
+    counts: HashMap<String, usize>,
+}
+
+impl FreqCounter {
+    pub fn new(options: FreqOptions) -> Self {
+        FreqCounter {
+            options,
+            words: 0,
+            counts: HashMap::new(),
+        }
+    }
+
+    pub fn add_line(&mut self, line: &str) {
+        for word in line.split_whitespace() {
+            let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+            if word.is_empty() || word.chars().count() < self.options.min_length {
+                continue;
+            }
+            self.words += 1;
+            let word = if self.options.case_sensitive {
+                word.to_string()
+            } else {
+                word.to_lowercase()
+            };
+            *self.counts.entry(word).or_insert(0) += 1;
+        }
+    }
+
+    /// Counts the words of a file, or of its selected region. A file that can't be read
+    /// to the end, or isn't UTF-8, adds nothing.
+    pub fn add_file(&mut self, path: &Path, region: Option<&Region>) -> Result<(), AnalyzerError> {
+        let mut reader = BufReader::new(region::open(path, region)?);
+        let mut file = FreqCounter::new(self.options.clone());
+        let mut line = Vec::new();
+        let mut offset = 0;
+        loop {
+            line.clear();
+            let n = reader
+                .read_until(b'\n', &mut line)
+                .map_err(|e| AnalyzerError::io(Some(path), e))?;
+            if n == 0 {
+                break;
+            }
+            let text = std::str::from_utf8(&line).map_err(|e| AnalyzerError::InvalidUtf8 {
+                path: Some(path.to_path_buf()),
+                offset: offset + e.valid_up_to() as u64,
+            })?;
+            file.add_line(text);
+            offset += n as u64;
+        }
+
+        self.words += file.words;
+        for (word, count) in file.counts {
+            *self.counts.entry(word).or_insert(0) += count;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Frequencies {
+        let distinct = self.counts.len();
+        let mut top: Vec<(String, usize)> = self.counts.into_iter().collect();
+        // Ties are broken alphabetically so the output is stable between runs.
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top.truncate(self.options.top);
+        Frequencies {
+            words: self.words,
+            distinct,
+            top,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_words() {
+        let mut counter = FreqCounter::new(FreqOptions {
+            top: 3,
+            min_length: 2,
+            ..FreqOptions::default()
+        });
+        counter.add_line("The cat, the hat and \"THE\" bat.");
+        counter.add_line("A cat -- a hat!");
+        let frequencies = counter.finish();
+        assert_eq!(frequencies.words, 9);
+        assert_eq!(frequencies.distinct, 5);
+        assert_eq!(
+            frequencies.top,
+            vec![
+                ("the".to_string(), 3),
+                ("cat".to_string(), 2),
+                ("hat".to_string(), 2),
+            ]
+        );
+
+        let mut counter = FreqCounter::new(FreqOptions {
+            case_sensitive: true,
+            ..FreqOptions::default()
+        });
+        counter.add_line("Word word");
+        assert_eq!(counter.finish().distinct, 2);
+    }
+
+    #[test]
+    fn test_invalid_file_adds_nothing() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let good = dir.path().join("good.txt");
+        let bad = dir.path().join("bad.txt");
+        std::fs::write(&good, "one two\n")?;
+        std::fs::write(&bad, b"three\nfo\xffur\n")?;
+
+        let mut counter = FreqCounter::new(FreqOptions::default());
+        counter.add_file(&good, None)?;
+        let err = counter.add_file(&bad, None).unwrap_err();
+        assert!(matches!(err, AnalyzerError::InvalidUtf8 { offset: 8, .. }));
+        assert_eq!(counter.finish().words, 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+use crate::analyzer::FileStats;
+use crate::report::{format_row, stats_row, FileReport};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum GroupBy {
+    /// Parent directory
+    Dir,
+    /// File extension
+    Ext,
+    /// Language, guessed from the extension
+    Lang,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SortKey {
+    Name,
+    Words,
+    Lines,
+    Chars,
+}
+
+impl SortKey {
+    /// Orders by name ascending, or by the metric with the largest first.
+    pub fn sort_by<T>(
+        self,
+        items: &mut [T],
+        name: impl Fn(&T) -> &str,
+        stats: impl Fn(&T) -> &FileStats,
+    ) {
+        match self {
+            SortKey::Name => items.sort_by(|a, b| name(a).cmp(name(b))),
+            SortKey::Words => items.sort_by_key(|item| std::cmp::Reverse(stats(item).words)),
+            SortKey::Lines => items.sort_by_key(|item| std::cmp::Reverse(stats(item).lines)),
+            SortKey::Chars => items.sort_by_key(|item| std::cmp::Reverse(stats(item).chars)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Group {
+    pub name: String,
+    pub files: usize,
+    #[serde(flatten)]
+    pub stats: FileStats,
+}
+
+/// Subtotals per group, ordered by name unless `sort` says otherwise.
+pub fn group_files(files: &[FileReport], by: GroupBy, sort: Option<SortKey>) -> Vec<Group> {
+    let mut groups: BTreeMap<String, Group> = BTreeMap::new();
+    for file in files {
+        let name = group_name(Path::new(&file.path), by);
+        let group = groups.entry(name.clone()).or_insert(Group {
+            name,
+            files: 0,
+            stats: FileStats::default(),
+        });
+        group.files += 1;
+        group.stats = group.stats + file.stats;
+    }
+
+    let mut groups: Vec<Group> = groups.into_values().collect();
+    if let Some(sort) = sort {
+        sort.sort_by(&mut groups, |g| &g.name, |g| &g.stats);
+    }
+    groups
+}
+
+fn group_name(path: &Path, by: GroupBy) -> String {
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    match by {
+        GroupBy::Dir => match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.display().to_string(),
+            _ => ".".to_string(),
+        },
+        GroupBy::Ext => match extension {
+            Some(ext) => format!(".{}", ext),
+            None => "(none)".to_string(),
+        },
+        GroupBy::Lang => language(extension.unwrap_or_default()).to_string(),
+    }
+}
+
+fn language(extension: &str) -> &'static str {
+    match extension.to_ascii_lowercase().as_str() {
+        "rs" => "Rust",
+        "py" => "Python",
+        "js" | "mjs" | "cjs" => "JavaScript",
+        "ts" | "tsx" => "TypeScript",
+        "go" => "Go",
+        "java" => "Java",
+        "kt" | "kts" => "Kotlin",
+        "rb" => "Ruby",
+        "c" | "h" => "C",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "C++",
+        "cs" => "C#",
+        "swift" => "Swift",
+        "sh" | "bash" | "zsh" => "Shell",
+        "md" | "markdown" => "Markdown",
+        "rst" => "reStructuredText",
+        "html" | "htm" => "HTML",
+        "css" | "scss" => "CSS",
+        "json" => "JSON",
+        "toml" => "TOML",
+        "yaml" | "yml" => "YAML",
+        "xml" => "XML",
+        "csv" | "tsv" => "CSV",
+        "sql" => "SQL",
+        "txt" => "Text",
+        _ => "Other",
+    }
+}
+
+/// A directory or file with the cumulative stats of everything below it.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TreeNode {
+    pub name: String,
+    pub files: usize,
+    #[serde(flatten)]
+    pub stats: FileStats,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+}
+
+#[derive(Default)]
+struct Node {
+    files: usize,
+    stats: FileStats,
+    children: BTreeMap<String, Node>,
+}
+
+/// Builds the directory tree of the given files. Directory names end with `/`.
+pub fn build_tree(files: &[FileReport], sort: Option<SortKey>) -> Vec<TreeNode> {
+    let mut root = Node::default();
+    for file in files {
+        let mut names: Vec<String> = Path::new(&file.path)
+            .components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let last = names.len().saturating_sub(1);
+        for name in &mut names[..last] {
+            if !name.ends_with('/') {
+                name.push('/');
+            }
+        }
+
+        let mut node = &mut root;
+        for name in names {
+            node = node.children.entry(name).or_default();
+            node.files += 1;
+            node.stats = node.stats + file.stats;
+        }
+    }
+
+    convert(root.children, sort)
+}
+
+fn convert(children: BTreeMap<String, Node>, sort: Option<SortKey>) -> Vec<TreeNode> {
+    let mut nodes: Vec<TreeNode> = children
+        .into_iter()
+        .map(|(name, node)| TreeNode {
+            name,
+            files: node.files,
+            stats: node.stats,
+            children: convert(node.children, sort),
+        })
+        .collect();
+    if let Some(sort) = sort {
+        sort.sort_by(&mut nodes, |n| &n.name, |n| &n.stats);
+    }
+    nodes
+}
+
+pub fn print_groups(groups: &[Group]) {
+    println!("{}", format_row("Words", "Lines", "Characters", "Group"));
+    for group in groups {
+        let noun = if group.files == 1 { "file" } else { "files" };
+        let name = format!("{} ({} {})", group.name, group.files, noun);
+        println!("{}", stats_row(&group.stats, &name));
+    }
+}
+
+pub fn print_tree(nodes: &[TreeNode]) {
+    println!("{}", format_row("Words", "Lines", "Characters", "Path"));
+    print_nodes(nodes, 0);
+}
+
+fn print_nodes(nodes: &[TreeNode], depth: usize) {
+    for node in nodes {
+        let name = format!("{}{}", "  ".repeat(depth), node.name);
+        println!("{}", stats_row(&node.stats, &name));
+        print_nodes(&node.children, depth + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, words: usize) -> FileReport {
+        FileReport {
+            path: path.to_string(),
+            stats: FileStats {
+                words,
+                lines: 1,
+                chars: words * 5,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn files() -> Vec<FileReport> {
+        vec![
+            file("src/main.rs", 10),
+            file("src/lib.rs", 30),
+            file("src/util/mod.rs", 5),
+            file("README.md", 100),
+            file("Makefile", 2),
+        ]
+    }
+
+    #[test]
+    fn test_group_by_ext_and_lang() {
+        let groups = group_files(&files(), GroupBy::Ext, None);
+        let names: Vec<_> = groups.iter().map(|g| (g.name.as_str(), g.files)).collect();
+        assert_eq!(names, vec![("(none)", 1), (".md", 1), (".rs", 3)]);
+        assert_eq!(groups[2].stats.words, 45);
+
+        let groups = group_files(&files(), GroupBy::Lang, Some(SortKey::Words));
+        let names: Vec<_> = groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, vec!["Markdown", "Rust", "Other"]);
+    }
+
+    #[test]
+    fn test_group_by_dir() {
+        let groups = group_files(&files(), GroupBy::Dir, None);
+        let names: Vec<_> = groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, vec![".", "src", "src/util"]);
+    }
+
+    #[test]
+    fn test_tree_is_cumulative() {
+        let tree = build_tree(&files(), Some(SortKey::Words));
+        let names: Vec<_> = tree.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, vec!["README.md", "src/", "Makefile"]);
+
+        let src = &tree[1];
+        assert_eq!(src.files, 3);
+        assert_eq!(src.stats.words, 45);
+        let children: Vec<_> = src.children.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(children, vec!["lib.rs", "main.rs", "util/"]);
+    }
+}
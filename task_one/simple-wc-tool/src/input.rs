@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Reads a list of paths separated by `separator`, from a file or from stdin for `-`.
+/// Empty entries, such as after a final separator, are skipped.
+pub fn read_file_list(source: &Path, separator: u8) -> Result<Vec<PathBuf>> {
+    let mut contents = Vec::new();
+    if source == Path::new("-") {
+        std::io::stdin()
+            .lock()
+            .read_to_end(&mut contents)
+            .context("Failed to read file list from stdin")?;
+    } else {
+        contents = fs::read(source)
+            .with_context(|| format!("Failed to read file list: {}", source.display()))?;
+    }
+    Ok(parse_file_list(&contents, separator))
+}
+
+fn parse_file_list(contents: &[u8], separator: u8) -> Vec<PathBuf> {
+    contents
+        .split(|&b| b == separator)
+        .map(|entry| match separator {
+            b'\n' => entry.strip_suffix(b"\r").unwrap_or(entry),
+            _ => entry,
+        })
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| PathBuf::from(os_string(entry)))
+        .collect()
+}
+
+#[cfg(unix)]
+fn os_string(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes.to_vec())
+}
+
+#[cfg(not(unix))]
+fn os_string(bytes: &[u8]) -> OsString {
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
+
+/// Expands the given paths into the list of files to analyze.
+///
+/// Files are kept as given. A directory contributes the files directly inside it,
+/// or every file below it when `recursive` is set. Hidden entries (starting with a dot)
+/// inside directories are skipped, and directory contents are sorted for stable output.
+pub fn collect_files(paths: &[PathBuf], recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            walk_dir(path, recursive, &mut files)?;
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+fn walk_dir(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?
+        .collect::<std::io::Result<Vec<_>>>()
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        if is_hidden(&entry.path()) {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if recursive {
+                walk_dir(&entry.path(), recursive, files)?;
+            }
+        } else if entry.path().is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("b.txt"), "b")?;
+        fs::write(dir.path().join("a.txt"), "a")?;
+        fs::write(dir.path().join(".hidden"), "h")?;
+        fs::create_dir(dir.path().join("sub"))?;
+        fs::write(dir.path().join("sub").join("c.txt"), "c")?;
+
+        let flat = collect_files(&[dir.path().to_path_buf()], false)?;
+        assert_eq!(
+            flat,
+            vec![dir.path().join("a.txt"), dir.path().join("b.txt")]
+        );
+
+        let nested = collect_files(&[dir.path().to_path_buf()], true)?;
+        assert_eq!(nested.len(), 3);
+        assert_eq!(nested[2], dir.path().join("sub").join("c.txt"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_file_list() {
+        assert_eq!(
+            parse_file_list(b"a.txt\0with\nnewline.txt\0", 0),
+            vec![PathBuf::from("a.txt"), PathBuf::from("with\nnewline.txt")]
+        );
+        assert_eq!(
+            parse_file_list(b"a.txt\r\n\nb c.txt", b'\n'),
+            vec![PathBuf::from("a.txt"), PathBuf::from("b c.txt")]
+        );
+        assert!(parse_file_list(b"", 0).is_empty());
+    }
+}
@@ -0,0 +1,387 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Letters looked at per input; the language of a document shows long before this.
+const MAX_LETTERS: usize = 100_000;
+
+/// Letters below which Latin or Cyrillic text says too little to tell its languages apart.
+const MIN_LETTERS: usize = 10;
+
+/// N-grams each letter takes part in. They overlap, so scores are divided by this before
+/// they become probabilities, which would otherwise be near certain for a few words.
+const NGRAMS_PER_LETTER: f64 = 3.0;
+
+/// Share of kana among CJK characters above which text is taken as Japanese.
+const KANA_SHARE: f64 = 0.1;
+
+/// One to three characters, padded with `\0`.
+type Ngram = [char; 3];
+
+/// Languages told apart by n-gram statistics, with the sample text each profile is built from.
+const PROFILES: [(&str, Script, &str); 12] = [
+    ("de", Script::Latin, include_str!("language/de.txt")),
+    ("en", Script::Latin, include_str!("language/en.txt")),
+    ("es", Script::Latin, include_str!("language/es.txt")),
+    ("fr", Script::Latin, include_str!("language/fr.txt")),
+    ("it", Script::Latin, include_str!("language/it.txt")),
+    ("nl", Script::Latin, include_str!("language/nl.txt")),
+    ("pl", Script::Latin, include_str!("language/pl.txt")),
+    ("pt", Script::Latin, include_str!("language/pt.txt")),
+    ("sv", Script::Latin, include_str!("language/sv.txt")),
+    ("tr", Script::Latin, include_str!("language/tr.txt")),
+    ("ru", Script::Cyrillic, include_str!("language/ru.txt")),
+    ("uk", Script::Cyrillic, include_str!("language/uk.txt")),
+];
+
+/// English name of a language detected by [`LanguageDetector`].
+pub fn name(code: &str) -> &'static str {
+    match code {
+        "ar" => "Arabic",
+        "de" => "German",
+        "el" => "Greek",
+        "en" => "English",
+        "es" => "Spanish",
+        "fr" => "French",
+        "he" => "Hebrew",
+        "hi" => "Hindi",
+        "it" => "Italian",
+        "ja" => "Japanese",
+        "ko" => "Korean",
+        "nl" => "Dutch",
+        "pl" => "Polish",
+        "pt" => "Portuguese",
+        "ru" => "Russian",
+        "sv" => "Swedish",
+        "th" => "Thai",
+        "tr" => "Turkish",
+        "uk" => "Ukrainian",
+        "zh" => "Chinese",
+        _ => "unknown",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Detection {
+    /// ISO 639-1 code, e.g. `en`.
+    pub language: String,
+    /// Between 0 and 1.
+    pub confidence: f64,
+}
+
+/// Scripts in the order they win a tie in letters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+    Hangul,
+    Kana,
+    Han,
+}
+
+impl Script {
+    fn of(c: char) -> Option<Script> {
+        let script = match c as u32 {
+            0x0370..=0x03FF => Script::Greek,
+            0x0400..=0x04FF => Script::Cyrillic,
+            0x0590..=0x05FF => Script::Hebrew,
+            0x0600..=0x06FF => Script::Arabic,
+            0x0900..=0x097F => Script::Devanagari,
+            0x0E00..=0x0E7F => Script::Thai,
+            0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Script::Hangul,
+            0x3040..=0x30FF => Script::Kana,
+            0x3400..=0x4DBF | 0x4E00..=0x9FFF => Script::Han,
+            _ if c.is_alphabetic()
+                && (c < '\u{0250}' || ('\u{1E00}'..='\u{1EFF}').contains(&c)) =>
+            {
+                Script::Latin
+            }
+            _ => return None,
+        };
+        Some(script)
+    }
+
+    /// The language of a script that only one supported language uses.
+    fn language(self) -> Option<&'static str> {
+        match self {
+            Script::Greek => Some("el"),
+            Script::Arabic => Some("ar"),
+            Script::Hebrew => Some("he"),
+            Script::Devanagari => Some("hi"),
+            Script::Thai => Some("th"),
+            Script::Hangul => Some("ko"),
+            _ => None,
+        }
+    }
+}
+
+/// Log-probabilities of n-grams in one language, with add-one smoothing.
+struct Profile {
+    language: &'static str,
+    script: Script,
+    log_probs: HashMap<Ngram, f64>,
+    unseen: f64,
+}
+
+fn profiles() -> &'static [Profile] {
+    static PROFILES_CELL: OnceLock<Vec<Profile>> = OnceLock::new();
+    PROFILES_CELL.get_or_init(|| {
+        let counted: Vec<_> = PROFILES
+            .iter()
+            .map(|&(language, script, text)| {
+                let mut ngrams = Ngrams::default();
+                text.chars().for_each(|c| ngrams.push(c));
+                (language, script, ngrams.counts)
+            })
+            .collect();
+        let vocabulary = counted
+            .iter()
+            .flat_map(|(_, _, counts)| counts.keys())
+            .collect::<std::collections::HashSet<_>>()
+            .len() as f64;
+
+        counted
+            .iter()
+            .map(|(language, script, counts)| {
+                let total = counts.values().sum::<u32>() as f64 + vocabulary;
+                Profile {
+                    language,
+                    script: *script,
+                    log_probs: counts
+                        .iter()
+                        .map(|(ngram, &count)| (*ngram, ((count as f64 + 1.0) / total).ln()))
+                        .collect(),
+                    unseen: (1.0 / total).ln(),
+                }
+            })
+            .collect()
+    })
+}
+
+/// Letter n-grams of lowercased words, up to trigrams, with words padded by a space.
+#[derive(Debug, Default, Clone)]
+struct Ngrams {
+    counts: HashMap<Ngram, u32>,
+    window: [char; 2],
+}
+
+impl Ngrams {
+    fn push(&mut self, c: char) {
+        let c = if c.is_alphabetic() {
+            c.to_lowercase().next().unwrap_or(c)
+        } else {
+            ' '
+        };
+        let [a, b] = self.window;
+        let b = if b == '\0' { ' ' } else { b };
+        if c == ' ' && b == ' ' {
+            return;
+        }
+        if c != ' ' {
+            *self.counts.entry([c, '\0', '\0']).or_default() += 1;
+        }
+        *self.counts.entry([b, c, '\0']).or_default() += 1;
+        if a != '\0' {
+            *self.counts.entry([a, b, c]).or_default() += 1;
+        }
+        self.window = [b, c];
+    }
+}
+
+/// Identifies the natural language of a text from its characters, fed in pieces.
+///
+/// Scripts used by a single language decide on their own, e.g. Hangul for Korean;
+/// Latin and Cyrillic text is classified with naive Bayes over letter n-grams.
+#[derive(Debug, Default, Clone)]
+pub struct LanguageDetector {
+    ngrams: Ngrams,
+    scripts: HashMap<Script, usize>,
+    letters: usize,
+}
+
+impl LanguageDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn chars(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.letters >= MAX_LETTERS {
+                return;
+            }
+            if let Some(script) = Script::of(c) {
+                *self.scripts.entry(script).or_default() += 1;
+                self.letters += 1;
+            }
+            self.ngrams.push(c);
+        }
+    }
+
+    /// The most likely language, or `None` for text without letters or with too few of
+    /// them to classify.
+    pub fn detect(&self) -> Option<Detection> {
+        let (&script, &count) = self
+            .scripts
+            .iter()
+            .max_by_key(|&(&script, &count)| (count, Reverse(script)))?;
+        let count_of = |script| self.scripts.get(&script).copied().unwrap_or(0) as f64;
+        let letters = self.letters as f64;
+        let detection = |language: &str, confidence: f64| Detection {
+            language: language.to_string(),
+            confidence: (confidence * 1000.0).round() / 1000.0,
+        };
+
+        match script {
+            Script::Han | Script::Kana => {
+                let cjk = count_of(Script::Han) + count_of(Script::Kana);
+                let language = if count_of(Script::Kana) > KANA_SHARE * cjk {
+                    "ja"
+                } else {
+                    "zh"
+                };
+                Some(detection(language, cjk / letters))
+            }
+            Script::Latin | Script::Cyrillic if count < MIN_LETTERS => None,
+            Script::Latin | Script::Cyrillic => {
+                let (language, probability) = self.classify(script)?;
+                Some(detection(language, probability * count as f64 / letters))
+            }
+            _ => Some(detection(script.language()?, count as f64 / letters)),
+        }
+    }
+
+    /// The likeliest language of `script` and its probability, which depends on how far
+    /// ahead of the other languages it is.
+    fn classify(&self, script: Script) -> Option<(&'static str, f64)> {
+        let scores: Vec<(&str, f64)> = profiles()
+            .iter()
+            .filter(|profile| profile.script == script)
+            .map(|profile| {
+                let score = self
+                    .ngrams
+                    .counts
+                    .iter()
+                    .map(|(ngram, &count)| {
+                        let log_prob = profile.log_probs.get(ngram).unwrap_or(&profile.unseen);
+                        count as f64 * log_prob
+                    })
+                    .sum::<f64>();
+                (profile.language, score)
+            })
+            .collect();
+
+        let &(language, best) = scores.iter().max_by(|a, b| a.1.total_cmp(&b.1))?;
+        let total: f64 = scores
+            .iter()
+            .map(|(_, score)| ((score - best) / NGRAMS_PER_LETTER).exp())
+            .sum();
+        Some((language, 1.0 / total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(text: &str) -> Option<Detection> {
+        let mut detector = LanguageDetector::new();
+        detector.chars(text);
+        detector.detect()
+    }
+
+    #[test]
+    fn test_detects_languages() {
+        let samples = [
+            (
+                "en",
+                "The children were playing in the garden while their parents talked.",
+            ),
+            (
+                "de",
+                "Die Kinder spielten im Garten, während ihre Eltern sich unterhielten.",
+            ),
+            (
+                "fr",
+                "Les enfants jouaient dans le jardin pendant que leurs parents discutaient.",
+            ),
+            (
+                "es",
+                "Los niños jugaban en el jardín mientras sus padres hablaban.",
+            ),
+            (
+                "it",
+                "I bambini giocavano in giardino mentre i loro genitori parlavano.",
+            ),
+            (
+                "pt",
+                "As crianças brincavam no jardim enquanto os pais conversavam.",
+            ),
+            (
+                "nl",
+                "De kinderen speelden in de tuin terwijl hun ouders met elkaar praatten.",
+            ),
+            (
+                "sv",
+                "Barnen lekte i trädgården medan deras föräldrar pratade med varandra.",
+            ),
+            (
+                "pl",
+                "Dzieci bawiły się w ogrodzie, a ich rodzice rozmawiali ze sobą.",
+            ),
+            (
+                "tr",
+                "Çocuklar bahçede oynarken anne ve babaları kendi aralarında konuşuyordu.",
+            ),
+            (
+                "ru",
+                "Дети играли в саду, пока их родители разговаривали между собой.",
+            ),
+            (
+                "uk",
+                "Діти гралися в саду, поки їхні батьки розмовляли між собою.",
+            ),
+            ("el", "Τα παιδιά έπαιζαν στον κήπο."),
+            ("ja", "子供たちは庭で遊んでいました。"),
+            ("zh", "孩子们在花园里玩耍。"),
+            ("ko", "아이들은 정원에서 놀고 있었다."),
+        ];
+        for (expected, text) in samples {
+            let detection = detect(text).unwrap();
+            assert_eq!(detection.language, expected, "{}", text);
+            assert!(detection.confidence > 0.5, "{}: {:?}", text, detection);
+        }
+    }
+
+    #[test]
+    fn test_short_text() {
+        assert_eq!(detect("Ok"), None);
+        let short = detect("Hello world").unwrap();
+        let long = detect("Hello world, how are you doing this morning?").unwrap();
+        assert_eq!(
+            (short.language.as_str(), long.language.as_str()),
+            ("en", "en")
+        );
+        assert!(short.confidence < long.confidence, "{:?}", short);
+    }
+
+    #[test]
+    fn test_script_ties() {
+        // Each detector hashes its scripts differently, so repeat to catch a random pick.
+        for _ in 0..20 {
+            assert_eq!(detect("αβγ אבג").unwrap().language, "el");
+            assert_eq!(detect("שלו 안녕하").unwrap().language, "he");
+        }
+    }
+
+    #[test]
+    fn test_no_letters() {
+        assert_eq!(detect("12 + 34 = 46\n"), None);
+        assert_eq!(detect(""), None);
+    }
+}
@@ -0,0 +1,23 @@
+//! Counting logic behind `simple-wc-tool`: words, lines and characters of streamed
+//! input, custom [`metric::Metric`]s such as BPE token counts, markup stripping, region
+//! selection and a fast path for line, character and byte counts.
+
+pub mod analyzer;
+#[cfg(feature = "async")]
+pub mod async_analyzer;
+pub mod dedup;
+pub mod delimited;
+pub mod entities;
+pub mod error;
+pub mod estimate;
+pub mod fast;
+pub mod freq;
+pub mod hll;
+pub mod language;
+pub mod lint;
+pub mod markup;
+pub mod metric;
+pub mod normalization;
+pub mod region;
+pub mod timeline;
+pub mod tokens;
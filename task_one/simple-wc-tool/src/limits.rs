@@ -0,0 +1,238 @@
+use crate::analyzer::FileStats;
+use crate::report::FileReport;
+use globset::{Glob, GlobMatcher};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    AtMost,
+    Below,
+    AtLeast,
+    Above,
+}
+
+impl Comparison {
+    fn holds(self, actual: usize, limit: usize) -> bool {
+        match self {
+            Comparison::AtMost => actual <= limit,
+            Comparison::Below => actual < limit,
+            Comparison::AtLeast => actual >= limit,
+            Comparison::Above => actual > limit,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Comparison::AtMost => "<=",
+            Comparison::Below => "<",
+            Comparison::AtLeast => ">=",
+            Comparison::Above => ">",
+        }
+    }
+}
+
+/// A threshold on one metric for the files matching a glob, written as
+/// `[GLOB:]METRIC OP VALUE`, e.g. `*.rs:lines<=1000` or `README.md:words<2000`.
+///
+/// A glob without a `/` is matched against the file name, otherwise against the
+/// whole path. Without a glob the limit applies to every file.
+#[derive(Debug, Clone)]
+pub struct Limit {
+    glob: Option<(String, GlobMatcher)>,
+    metric: String,
+    comparison: Comparison,
+    value: usize,
+}
+
+impl Limit {
+    /// The metric this limit is on, one of [`FileStats::METRICS`].
+    pub fn metric(&self) -> &str {
+        &self.metric
+    }
+
+    fn applies_to(&self, path: &Path) -> bool {
+        match &self.glob {
+            None => true,
+            Some((pattern, matcher)) if pattern.contains('/') => matcher.is_match(path),
+            Some((_, matcher)) => path
+                .file_name()
+                .is_some_and(|name| matcher.is_match(Path::new(name))),
+        }
+    }
+}
+
+impl FromStr for Limit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (glob, condition) = match s.rsplit_once(':') {
+            Some((glob, condition)) => (Some(glob.trim()), condition),
+            None => (None, s),
+        };
+
+        let (op_start, comparison, op_len) = [
+            ("<=", Comparison::AtMost),
+            (">=", Comparison::AtLeast),
+            ("<", Comparison::Below),
+            (">", Comparison::Above),
+        ]
+        .iter()
+        .find_map(|(op, comparison)| condition.find(op).map(|i| (i, *comparison, op.len())))
+        .ok_or_else(|| format!("limit '{}' has no comparison (<=, <, >=, >)", s))?;
+
+        let metric = condition[..op_start].trim();
+        if FileStats::default().metric(metric).is_none() {
+            return Err(format!(
+                "unknown metric '{}' in limit '{}' (expected one of: {})",
+                metric,
+                s,
+                FileStats::METRICS.join(", ")
+            ));
+        }
+
+        let value = condition[op_start + op_len..]
+            .trim()
+            .parse()
+            .map_err(|_| format!("limit '{}' needs a whole number after the comparison", s))?;
+
+        let glob = match glob {
+            Some(pattern) => {
+                let matcher = Glob::new(pattern)
+                    .map_err(|e| format!("invalid glob in limit '{}': {}", s, e))?
+                    .compile_matcher();
+                Some((pattern.to_string(), matcher))
+            }
+            None => None,
+        };
+
+        Ok(Limit {
+            glob,
+            metric: metric.to_string(),
+            comparison,
+            value,
+        })
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((pattern, _)) = &self.glob {
+            write!(f, "{}:", pattern)?;
+        }
+        write!(
+            f,
+            "{}{}{}",
+            self.metric,
+            self.comparison.symbol(),
+            self.value
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Violation {
+    pub path: String,
+    pub metric: String,
+    pub actual: usize,
+    pub limit: String,
+}
+
+/// Prints the violations under a heading, if there are any.
+pub fn print_violations(violations: &[Violation]) {
+    if violations.is_empty() {
+        return;
+    }
+    println!("Limit violations:");
+    for violation in violations {
+        println!(
+            "  {}: {} is {} (limit {})",
+            violation.path, violation.metric, violation.actual, violation.limit
+        );
+    }
+}
+
+/// Checks every file against every limit that applies to it.
+pub fn check(limits: &[Limit], files: &[FileReport]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for file in files {
+        for limit in limits {
+            if !limit.applies_to(Path::new(&file.path)) {
+                continue;
+            }
+            let actual = file.stats.metric(&limit.metric).unwrap_or_default();
+            if !limit.comparison.holds(actual, limit.value) {
+                violations.push(Violation {
+                    path: file.path.clone(),
+                    metric: limit.metric.clone(),
+                    actual,
+                    limit: limit.to_string(),
+                });
+            }
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, words: usize, lines: usize) -> FileReport {
+        FileReport {
+            path: path.to_string(),
+            stats: FileStats {
+                words,
+                lines,
+                chars: 0,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let limit: Limit = "*.rs: lines <= 1000".parse().unwrap();
+        assert_eq!(limit.to_string(), "*.rs:lines<=1000");
+
+        let limit: Limit = "words>10".parse().unwrap();
+        assert!(limit.glob.is_none());
+        assert_eq!(limit.comparison, Comparison::Above);
+
+        assert!("*.rs:lines".parse::<Limit>().is_err());
+        assert!("*.rs:pages<=3".parse::<Limit>().is_err());
+        assert!("*.rs:lines<=many".parse::<Limit>().is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let limits: Vec<Limit> = vec![
+            "*.rs:lines<=1000".parse().unwrap(),
+            "README.md:words<2000".parse().unwrap(),
+            "docs/**/*.md:words>=10".parse().unwrap(),
+        ];
+        let files = vec![
+            file("src/main.rs", 5000, 1200),
+            file("src/lib.rs", 100, 10),
+            file("./README.md", 2000, 50),
+            file("docs/guide/intro.md", 3, 1),
+            file("other/intro.md", 3, 1),
+        ];
+
+        let violations = check(&limits, &files);
+        let found: Vec<_> = violations
+            .iter()
+            .map(|v| (v.path.as_str(), v.metric.as_str(), v.actual))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("src/main.rs", "lines", 1200),
+                ("./README.md", "words", 2000),
+                ("docs/guide/intro.md", "words", 3),
+            ]
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! Checks of line endings and whitespace, reported per line like a compiler would.
+
+use crate::error::AnalyzerError;
+use crate::region::{self, Region};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Check {
+    /// Every line ends the same way, as `--eol` asks or as most lines of the file do.
+    Eol,
+    /// The file ends with a line break.
+    FinalNewline,
+    /// No line ends in spaces or tabs.
+    TrailingWhitespace,
+}
+
+impl Check {
+    pub const ALL: [Check; 3] = [Check::Eol, Check::FinalNewline, Check::TrailingWhitespace];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Check::Eol => "eol",
+            Check::FinalNewline => "final-newline",
+            Check::TrailingWhitespace => "trailing-whitespace",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LintOptions {
+    pub checks: Vec<Check>,
+    /// The line ending every line should have. Without one, the more common one of each file.
+    pub eol: Option<LineEnding>,
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        LintOptions {
+            checks: Check::ALL.to_vec(),
+            eol: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Issue {
+    pub path: String,
+    /// Line number, counted from 1.
+    pub line: usize,
+    pub check: Check,
+    pub message: String,
+}
+
+/// Collects the issues of one input, fed line by line with the line breaks.
+#[derive(Debug)]
+pub struct Linter {
+    options: LintOptions,
+    lines: usize,
+    /// Line numbers of the first line with each ending, and how many there are.
+    lf: (Option<usize>, usize),
+    crlf: (Option<usize>, usize),
+    terminated: bool,
+    issues: Vec<(usize, Check, String)>,
+}
+
+impl Linter {
+    pub fn new(options: LintOptions) -> Self {
+        Linter {
+            options,
+            lines: 0,
+            lf: (None, 0),
+            crlf: (None, 0),
+            terminated: true,
+            issues: Vec::new(),
+        }
+    }
+
+    /// Checks the next line, including its line break if it has one.
+    pub fn line(&mut self, line: &[u8]) {
+        self.lines += 1;
+        let (content, ending) = match line.strip_suffix(b"\n") {
+            Some(rest) => match rest.strip_suffix(b"\r") {
+                Some(rest) => (rest, Some(LineEnding::Crlf)),
+                None => (rest, Some(LineEnding::Lf)),
+            },
+            None => (line, None),
+        };
+        self.terminated = ending.is_some();
+        let count = match ending {
+            Some(LineEnding::Lf) => Some(&mut self.lf),
+            Some(LineEnding::Crlf) => Some(&mut self.crlf),
+            None => None,
+        };
+        if let Some((first, count)) = count {
+            first.get_or_insert(self.lines);
+            *count += 1;
+        }
+
+        if self.enabled(Check::TrailingWhitespace) && matches!(content.last(), Some(b' ' | b'\t')) {
+            let message = "trailing whitespace".to_string();
+            self.issues
+                .push((self.lines, Check::TrailingWhitespace, message));
+        }
+    }
+
+    /// The issues found, in line order.
+    pub fn finish(mut self, path: &str) -> Vec<Issue> {
+        if self.enabled(Check::Eol) {
+            let expected = self.options.eol.unwrap_or(if self.crlf.1 > self.lf.1 {
+                LineEnding::Crlf
+            } else {
+                LineEnding::Lf
+            });
+            let (wrong, (first, count)) = match expected {
+                LineEnding::Lf => (LineEnding::Crlf, self.crlf),
+                LineEnding::Crlf => (LineEnding::Lf, self.lf),
+            };
+            if let Some(first) = first {
+                let message = format!(
+                    "{} of {} lines end in {}, expected {}",
+                    count,
+                    self.lf.1 + self.crlf.1,
+                    wrong.name(),
+                    expected.name()
+                );
+                self.issues.push((first, Check::Eol, message));
+            }
+        }
+        if self.enabled(Check::FinalNewline) && !self.terminated {
+            let message = "no newline at end of file".to_string();
+            self.issues.push((self.lines, Check::FinalNewline, message));
+        }
+
+        self.issues
+            .sort_by_key(|&(line, check, _)| (line, check as u8));
+        self.issues
+            .into_iter()
+            .map(|(line, check, message)| Issue {
+                path: path.to_string(),
+                line,
+                check,
+                message,
+            })
+            .collect()
+    }
+
+    fn enabled(&self, check: Check) -> bool {
+        self.options.checks.contains(&check)
+    }
+}
+
+/// Checks a file, or its selected region.
+pub fn lint_file(
+    path: &Path,
+    options: &LintOptions,
+    region: Option<&Region>,
+) -> Result<Vec<Issue>, AnalyzerError> {
+    let mut reader = BufReader::new(region::open(path, region)?);
+    let mut linter = Linter::new(options.clone());
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let n = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|e| AnalyzerError::io(Some(path), e))?;
+        if n == 0 {
+            break;
+        }
+        linter.line(&line);
+    }
+    Ok(linter.finish(&path.display().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(input: &str, options: LintOptions) -> Vec<(usize, Check)> {
+        let mut linter = Linter::new(options);
+        for line in input.split_inclusive('\n') {
+            linter.line(line.as_bytes());
+        }
+        linter
+            .finish("t.txt")
+            .into_iter()
+            .map(|issue| (issue.line, issue.check))
+            .collect()
+    }
+
+    #[test]
+    fn test_checks() {
+        let input = "one\ntwo \r\nthree\nfour\t\nfive";
+        assert_eq!(
+            lint(input, LintOptions::default()),
+            vec![
+                (2, Check::Eol),
+                (2, Check::TrailingWhitespace),
+                (4, Check::TrailingWhitespace),
+                (5, Check::FinalNewline),
+            ]
+        );
+
+        let crlf = LintOptions {
+            checks: vec![Check::Eol],
+            eol: Some(LineEnding::Crlf),
+        };
+        assert_eq!(lint(input, crlf.clone()), vec![(1, Check::Eol)]);
+        assert_eq!(lint("a\r\nb\r\n", crlf), vec![]);
+        assert_eq!(lint("", LintOptions::default()), vec![]);
+    }
+}
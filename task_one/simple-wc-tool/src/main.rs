@@ -1,27 +1,1470 @@
-use clap::Parser;
-use std::path::PathBuf;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use regex::Regex;
+use simple_wc_tool::entities::Entities;
+use simple_wc_tool::error::AnalyzerError;
+use simple_wc_tool::estimate::{self, Estimate};
+use simple_wc_tool::language;
+use simple_wc_tool::normalization::Normalization;
+use simple_wc_tool::{
+    analyzer, dedup, delimited, fast, freq, lint, markup, metric, region, timeline, tokens,
+};
+use std::collections::BTreeMap;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 
-mod analyzer;
+mod cache;
+mod compare;
+mod config;
+mod follow;
+mod group;
+mod input;
+mod limits;
+mod progress;
+mod report;
+mod serve;
+mod watch;
+
+/// Exit code when files violate a `--limit` or fail `lint` checks, distinct from runtime (1)
+/// and usage (2) errors.
+const LIMIT_VIOLATION_EXIT_CODE: u8 = 3;
+
+/// Exit code after Ctrl-C, as for a shell command killed by SIGINT.
+const INTERRUPTED_EXIT_CODE: u8 = 130;
+
+/// Exit code for a file that couldn't be analyzed, by error kind. Other errors exit with 1.
+fn file_error_exit_code(kind: report::ErrorKind) -> u8 {
+    match kind {
+        report::ErrorKind::NotFound => 4,
+        report::ErrorKind::PermissionDenied => 5,
+        report::ErrorKind::InvalidUtf8 => 6,
+        report::ErrorKind::Io | report::ErrorKind::Other => 1,
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+    /// One row per file, or per time bucket with --timestamps
+    Csv,
+}
+
+/// Which files to read, shared by every subcommand that reads files.
+#[derive(clap::Args, Debug)]
+struct InputArgs {
+    /// Files to analyze; directories contribute the files inside them
+    files: Vec<PathBuf>,
+
+    /// Also analyze the paths listed in FILE, one per line; '-' reads the list from stdin
+    #[arg(long, value_name = "FILE")]
+    files_from: Option<PathBuf>,
+
+    /// Like --files-from, with paths separated by NUL bytes as from `find -print0`
+    #[arg(long, value_name = "FILE", conflicts_with = "files_from")]
+    files0_from: Option<PathBuf>,
+
+    /// Descend into subdirectories
+    #[arg(short, long, overrides_with = "no_recursive")]
+    recursive: bool,
+
+    #[arg(long, overrides_with = "recursive", hide = true)]
+    no_recursive: bool,
+
+    /// Only analyze these lines, numbered from 1 with both ends included, e.g. 100..500 or 100..
+    #[arg(long, value_name = "START..END", conflicts_with_all = ["bytes", "between"])]
+    lines: Option<region::Range>,
+
+    /// Only analyze these byte offsets, end excluded, e.g. 0..4096
+    #[arg(long, value_name = "START..END", conflicts_with = "between")]
+    bytes: Option<region::Range>,
+
+    /// Only analyze the lines between a line matching START and one matching END
+    #[arg(long, num_args = 2, value_names = ["START", "END"])]
+    between: Vec<Regex>,
+}
+
+impl InputArgs {
+    fn region(&self) -> Option<region::Region> {
+        if let Some(range) = self.lines {
+            Some(region::Region::Lines(range))
+        } else if let Some(range) = self.bytes {
+            Some(region::Region::Bytes(range))
+        } else if let [start, end] = self.between.as_slice() {
+            Some(region::Region::Between(start.clone(), end.clone()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Where options and cached stats come from, shared like [`InputArgs`].
+#[derive(clap::Args, Debug)]
+struct ConfigArgs {
+    /// Don't read or update the stats cache
+    #[arg(long, overrides_with = "cache")]
+    no_cache: bool,
+
+    #[arg(long, overrides_with = "no_cache", hide = true)]
+    cache: bool,
+
+    /// Apply a named profile from the config files
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Ignore .simple-wc.toml and the user config
+    #[arg(long, conflicts_with = "profile")]
+    no_config: bool,
+}
 
 #[derive(Parser, Debug)]
 #[command(name = "simple-wc-tool")]
 #[command(version = "0.1.0")]
 #[command(author = "Vladislav Dyachenko")]
 #[command(about = "File content analyzer")]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    count: Args,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Count lines, words, characters and more, like wc; the default without a subcommand
+    Count(Box<Args>),
+    /// List the most frequent words
+    Freq(FreqArgs),
+    /// Check line endings, trailing whitespace and --limit thresholds
+    Lint(LintArgs),
+    /// Print subtotals per directory, extension or language, or a directory tree
+    Report(ReportArgs),
+    /// Show per-file and total changes since a baseline saved with --save-baseline
+    Compare(CompareArgs),
+    /// Serve the counts over HTTP: POST text or multipart files to /analyze for JSON stats
+    Serve(serve::ServeArgs),
+}
+
+/// Options of `count`. The other subcommands take some of them, under the same names
+/// so that config files apply to them too, and run like `count` with the rest left
+/// at their defaults.
+#[derive(clap::Args, Debug)]
 struct Args {
-    file_path: PathBuf,
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Report distinct, unique and duplicated line counts
+    #[arg(long, overrides_with = "no_dup_stats")]
+    dup_stats: bool,
+
+    #[arg(long, overrides_with = "dup_stats", hide = true)]
+    no_dup_stats: bool,
+
+    /// Number of most repeated lines to list with --dup-stats
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    top_lines: usize,
+
+    /// Normalize lines before comparing them: trim, case, digits, timestamps
+    #[arg(long, value_name = "KIND", value_delimiter = ',')]
+    normalize_lines: Vec<dedup::LineNormalization>,
+
+    /// Estimate the distinct line count with HyperLogLog instead of keeping every line in memory
+    #[arg(long, overrides_with = "no_approx")]
+    approx: bool,
+
+    #[arg(long, overrides_with = "approx", hide = true)]
+    no_approx: bool,
+
+    /// Keep the file open and report counts as data is appended, like `tail -f`
+    #[arg(short, long, conflicts_with_all = ["dup_stats", "lines", "bytes", "between", "metrics"])]
+    follow: bool,
+
+    /// Seconds between updates in --follow mode
+    #[arg(long, value_name = "SECS", default_value = "1", value_parser = parse_interval)]
+    interval: Duration,
+
+    /// Also compare content hashes before trusting cached stats
+    #[arg(long, conflicts_with = "no_cache", overrides_with = "no_cache_hash")]
+    cache_hash: bool,
+
+    #[arg(long, overrides_with = "cache_hash", hide = true)]
+    no_cache_hash: bool,
+
+    /// Remove cache entries of files that no longer exist
+    #[arg(long, conflicts_with = "no_cache")]
+    prune_cache: bool,
+
+    /// Re-analyze files whenever they change on disk
+    #[arg(short, long, conflicts_with_all = [
+        "dup_stats", "follow", "lines", "bytes", "between", "metrics",
+    ])]
+    watch: bool,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Save the per-file stats of this run as a JSON baseline
+    #[arg(long, value_name = "FILE")]
+    save_baseline: Option<PathBuf>,
+
+    /// Show per-file and total changes since a baseline saved with --save-baseline
+    #[arg(long, value_name = "FILE")]
+    compare: Option<PathBuf>,
+
+    /// Fail when a file exceeds a threshold, e.g. '*.rs:lines<=1000' or 'README.md:words<2000'
+    #[arg(long, value_name = "[GLOB:]METRIC<=N")]
+    limit: Vec<limits::Limit>,
+
+    /// Print subtotals per directory, extension or language
+    #[arg(long, value_enum, value_name = "KEY")]
+    group_by: Option<group::GroupBy>,
+
+    /// Print a directory tree with cumulative stats per level
+    #[arg(long, overrides_with = "no_tree")]
+    tree: bool,
+
+    #[arg(long, overrides_with = "tree", hide = true)]
+    no_tree: bool,
+
+    /// Order files, groups and tree entries by name or by a metric, largest first
+    #[arg(long, value_enum, value_name = "KEY")]
+    sort: Option<group::SortKey>,
+
+    /// Only list the N largest files (by --sort, or by words)
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+
+    /// Count only the visible text of Markdown or HTML files; code blocks are counted separately
+    #[arg(long, value_enum, value_name = "KIND")]
+    markup: Option<markup::MarkupMode>,
+
+    /// Report records, fields and per-column stats of CSV or TSV files
+    #[arg(long, value_enum, value_name = "KIND")]
+    delimited: Option<delimited::DelimitedMode>,
+
+    /// With --delimited, treat the first record as data rather than column names
+    #[arg(long, overrides_with = "header")]
+    no_header: bool,
+
+    #[arg(long, overrides_with = "no_header", hide = true)]
+    header: bool,
+
+    /// Also count LLM tokens, using a local BPE vocabulary: a tiktoken rank file,
+    /// a merges.txt or a vocab.json / tokenizer.json
+    #[arg(long, value_name = "VOCAB_FILE")]
+    tokens: Option<PathBuf>,
+
+    /// Unicode-normalize the text before counting, and report whether it already was
+    #[arg(long, value_enum, value_name = "FORM")]
+    normalize: Option<Normalization>,
+
+    /// Identify the natural language of each file, with a confidence score
+    #[arg(long, overrides_with = "no_detect_language")]
+    detect_language: bool,
+
+    #[arg(long, overrides_with = "detect_language", hide = true)]
+    no_detect_language: bool,
+
+    /// Bucket lines and bytes by the timestamp at the start of each line
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["follow", "watch"])]
+    timestamps: Option<timeline::TimestampFormat>,
+
+    /// Bucket width for --timestamps, e.g. 30s, 5m, 1h or 1d
+    #[arg(long, value_name = "DURATION", default_value = "1m", value_parser = timeline::parse_duration)]
+    bucket: u64,
+
+    /// Count emoji, URLs, email addresses, hashtags, mentions and numbers
+    #[arg(long, overrides_with = "no_entities")]
+    entities: bool,
+
+    #[arg(long, overrides_with = "entities", hide = true)]
+    no_entities: bool,
+
+    /// Number of most common values to list per entity type with --entities
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    top_entities: usize,
+
+    /// Counts to show, e.g. 'lines,bytes'; without words, plain files are counted by a faster byte scan
+    #[arg(long, value_enum, value_name = "LIST", value_delimiter = ',', default_values_t = report::Column::DEFAULT)]
+    metrics: Vec<report::Column>,
+
+    /// Estimate words, lines and characters of large files from random samples
+    #[arg(long, conflicts_with_all = [
+        "follow", "watch", "dup_stats", "lines", "bytes", "between", "markup", "delimited",
+        "tokens", "normalize", "detect_language", "entities", "timestamps", "save_baseline",
+        "compare",
+    ])]
+    estimate: bool,
+
+    /// Bytes to read per file with --estimate, e.g. 64M; smaller files are counted exactly
+    #[arg(long, value_name = "SIZE", default_value = "8M", value_parser = parse_size)]
+    sample_budget: u64,
+
+    #[command(flatten)]
+    config: ConfigArgs,
 }
 
-fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+#[derive(clap::Args, Debug)]
+struct FreqArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Number of most frequent words to list
+    #[arg(long = "top", value_name = "N", default_value_t = 20)]
+    top_words: usize,
+
+    /// Leave out words shorter than N characters
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    min_length: usize,
+
+    /// Count 'Word' and 'word' as different words
+    #[arg(long)]
+    case_sensitive: bool,
+
+    /// Output format; CSV has one row per word
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    #[command(flatten)]
+    config: ConfigArgs,
+}
 
-    match analyzer::analyze_file(&args.file_path) {
-        Ok(stats) => {
-            println!("Words: {}", stats.words);
-            println!("Lines: {}", stats.lines);
-            println!("Characters: {}", stats.chars);
-            Ok(())
+#[derive(clap::Args, Debug)]
+struct LintArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Checks to run
+    #[arg(long, value_enum, value_name = "LIST", value_delimiter = ',', default_values_t = lint::Check::ALL)]
+    checks: Vec<lint::Check>,
+
+    /// Line ending every line should have; by default the more common one in each file
+    #[arg(long, value_enum)]
+    eol: Option<lint::LineEnding>,
+
+    /// Fail when a file exceeds a threshold, e.g. '*.rs:lines<=1000' or 'README.md:words<2000'
+    #[arg(long, value_name = "[GLOB:]METRIC<=N")]
+    limit: Vec<limits::Limit>,
+
+    /// Output format; CSV has one row per problem
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct ReportArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Print subtotals per directory, extension or language; by directory unless --tree is given
+    #[arg(long = "by", value_enum, value_name = "KEY")]
+    group_by: Option<group::GroupBy>,
+
+    /// Print a directory tree with cumulative stats per level
+    #[arg(long, overrides_with = "no_tree")]
+    tree: bool,
+
+    #[arg(long, overrides_with = "tree", hide = true)]
+    no_tree: bool,
+
+    /// Order files, groups and tree entries by name or by a metric, largest first
+    #[arg(long, value_enum, value_name = "KEY")]
+    sort: Option<group::SortKey>,
+
+    /// Only list the N largest files (by --sort, or by words)
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+
+    /// Counts to show, e.g. 'lines,bytes'
+    #[arg(long, value_enum, value_name = "LIST", value_delimiter = ',', default_values_t = report::Column::DEFAULT)]
+    metrics: Vec<report::Column>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct CompareArgs {
+    /// Baseline saved with --save-baseline
+    baseline: PathBuf,
+
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+/// What a run prints. Every mode counts the files first, for the errors and limits.
+enum Mode {
+    Count,
+    Freq(freq::FreqOptions),
+    Lint(lint::LintOptions),
+}
+
+impl Args {
+    /// Every option at its default, as a subcommand without it needs it.
+    fn defaults() -> Args {
+        let command = <Args as clap::Args>::augment_args(clap::Command::new("simple-wc-tool"));
+        Args::from_arg_matches(&command.get_matches_from(["simple-wc-tool"]))
+            .expect("every option has a default")
+    }
+}
+
+impl FreqArgs {
+    fn into_args(self) -> (Args, Mode) {
+        let options = freq::FreqOptions {
+            top: self.top_words,
+            min_length: self.min_length,
+            case_sensitive: self.case_sensitive,
+        };
+        // Only lines are counted, so plain files take the fast path before the word pass.
+        let args = Args {
+            input: self.input,
+            metrics: vec![report::Column::Lines],
+            format: self.format,
+            config: self.config,
+            ..Args::defaults()
+        };
+        (args, Mode::Freq(options))
+    }
+}
+
+impl LintArgs {
+    fn into_args(self) -> (Args, Mode) {
+        let options = lint::LintOptions {
+            checks: self.checks,
+            eol: self.eol,
+        };
+        let args = Args {
+            input: self.input,
+            metrics: vec![report::Column::Lines],
+            limit: self.limit,
+            format: self.format,
+            config: self.config,
+            ..Args::defaults()
+        };
+        (args, Mode::Lint(options))
+    }
+}
+
+impl ReportArgs {
+    fn into_args(self) -> Args {
+        let group_by = self
+            .group_by
+            .or((!self.tree).then_some(group::GroupBy::Dir));
+        Args {
+            input: self.input,
+            group_by,
+            tree: self.tree,
+            sort: self.sort,
+            top: self.top,
+            metrics: self.metrics,
+            format: self.format,
+            config: self.config,
+            ..Args::defaults()
+        }
+    }
+}
+
+impl CompareArgs {
+    fn into_args(self) -> Args {
+        Args {
+            input: self.input,
+            compare: Some(self.baseline),
+            format: self.format,
+            config: self.config,
+            ..Args::defaults()
+        }
+    }
+}
+
+/// Parses a number of seconds, like `0.5`, for `--interval`.
+fn parse_interval(s: &str) -> Result<Duration, String> {
+    let secs = s
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("'{}' is not a number of seconds", s))?;
+    interval_from_secs(secs)
+}
+
+/// An interval of `secs` seconds, which has to be finite and above zero; zero would poll
+/// without pausing.
+fn interval_from_secs(secs: f64) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(secs)
+        .ok()
+        .filter(|interval| !interval.is_zero())
+        .ok_or_else(|| "not a finite number of seconds above 0".to_string())
+}
+
+/// Parses a byte count with an optional K, M or G suffix (powers of 1024).
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (digits, shift) = match s.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&s[..i], 10),
+        Some((i, 'M' | 'm')) => (&s[..i], 20),
+        Some((i, 'G' | 'g')) => (&s[..i], 30),
+        _ => (s, 0),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .filter(|&n| n > 0)
+        .ok_or_else(|| format!("'{}' is not a size like 4096, 512K or 64M", s))
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches)?;
+    if let Some(name) = misplaced_subcommand(&cli.count.input.files) {
+        let message = format!(
+            "'{name}' is a subcommand; options go after it, as in `simple-wc-tool {name} \
+             [OPTIONS] [FILES]...`, and a file named '{name}' goes after `--`"
+        );
+        Cli::command()
+            .error(clap::error::ErrorKind::ArgumentConflict, message)
+            .exit();
+    }
+    let (mut args, mode) = match cli.command {
+        None => (cli.count, Mode::Count),
+        Some(Command::Count(args)) => (*args, Mode::Count),
+        Some(Command::Freq(freq)) => freq.into_args(),
+        Some(Command::Lint(lint)) => lint.into_args(),
+        Some(Command::Report(report)) => (report.into_args(), Mode::Count),
+        Some(Command::Compare(compare)) => (compare.into_args(), Mode::Count),
+        Some(Command::Serve(serve_args)) => {
+            serve::run(&serve_args)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+    };
+
+    // Options missing on the command line are filled in from the profile, then the config files.
+    let mut command = Cli::command();
+    command.build();
+    let (mut command, matches) = match matches.subcommand() {
+        Some((name, matches)) => {
+            let subcommand = command.find_subcommand(name).expect("parsed subcommand");
+            (subcommand.clone(), matches)
+        }
+        None => (command, &matches),
+    };
+    if !args.config.no_config {
+        config::load()?
+            .resolve(args.config.profile.as_deref())?
+            .apply(&mut args, &command, matches)?;
+    }
+
+    // Listed paths go through the same pipeline as the ones given as arguments.
+    let input = &mut args.input;
+    let file_list = match (&input.files_from, &input.files0_from) {
+        (Some(source), _) => Some(input::read_file_list(source, b'\n')?),
+        (_, Some(source)) => Some(input::read_file_list(source, b'\0')?),
+        (None, None) => None,
+    };
+    let listed = file_list.is_some();
+    input.files.extend(file_list.unwrap_or_default());
+    if input.files.is_empty() && !listed {
+        // Reported like a missing argument of the command that was run, with its usage.
+        command
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "no files given; pass FILES, --files-from or --files0-from",
+            )
+            .exit();
+    }
+
+    // Watched and followed files are counted like in a plain run, e.g. with --markup.
+    let markup_for = |file: &Path| {
+        args.markup
+            .and_then(|mode| markup::Markup::for_path(file, mode))
+    };
+    if args.watch {
+        let counting = Counting::new(&args)?;
+        let analyze = |file: &Path| {
+            let analysis =
+                counting.analyze(file, markup_for(file), &mut |_| ControlFlow::Continue(()))?;
+            Ok(analysis.stats)
+        };
+        watch::run(&args.input.files, args.input.recursive, analyze)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let files = input::collect_files(&args.input.files, args.input.recursive)?;
+
+    if args.follow {
+        let [file] = files.as_slice() else {
+            anyhow::bail!("--follow takes exactly one file");
+        };
+        let analyzer = Counting::new(&args)?.analyzer(markup_for(file));
+        follow::run(file, analyzer, args.interval)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut cache = cache::default_path()
+        .filter(|_| !args.config.no_cache)
+        .map(|path| cache::Cache::load(&path));
+    if let Some(cache) = cache.as_mut().filter(|_| args.prune_cache) {
+        let removed = cache.prune();
+        eprintln!("Pruned {} stale cache entries", removed);
+    }
+
+    progress::handle_interrupts()?;
+    let counting = Counting::new(&args)?;
+    let region = counting.region.as_ref();
+    let mut results = Vec::new();
+    // A file that fails is reported and left out; the others are still analyzed.
+    let mut errors = Vec::new();
+    let mut analyzed = Vec::new();
+    let mut progress = progress::Progress::new(&files);
+    for file in &files {
+        // After Ctrl-C, report what was counted so far.
+        if progress::interrupted() {
+            break;
+        }
+        // Ctrl-C also stops reading a large file, which then counts as far as it got.
+        let mut stopped = false;
+        let mut on_chunk = |bytes| {
+            progress.read(bytes);
+            if progress::interrupted() {
+                stopped = true;
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        };
+        match analyze_one(file, &args, &counting, cache.as_mut(), &mut on_chunk) {
+            Ok(mut result) => {
+                result.partial = stopped;
+                results.push(result);
+                analyzed.push(file.clone());
+            }
+            Err(err) => {
+                progress.clear();
+                eprintln!("simple-wc-tool: {:#}", err);
+                errors.push(report::FileError::new(file, &err));
+            }
+        }
+        progress.file_done();
+    }
+    progress.clear();
+    let incomplete = progress::interrupted();
+    if incomplete {
+        eprintln!(
+            "Interrupted after {} of {} files; totals are incomplete",
+            analyzed.len() + errors.len(),
+            files.len()
+        );
+        if let Some(file) = results.iter().find(|file| file.partial) {
+            eprintln!("{} was only partly read", file.path);
+        }
+    }
+    // The counts don't depend on the cache, so they're still printed if it can't be written.
+    if let Some(Err(err)) = cache.as_ref().map(cache::Cache::save) {
+        eprintln!("simple-wc-tool: warning: {:#}", err);
+    }
+
+    let mut report = report::Report::new(results);
+    report.errors = errors;
+    report.estimate = report
+        .files
+        .iter()
+        .filter_map(|file| file.estimate)
+        .reduce(|a, b| a + b);
+    report.incomplete = incomplete;
+    // A partial run skips the extra passes and isn't kept as a baseline.
+    if args.dup_stats && !incomplete {
+        let options = dedup::DupOptions {
+            normalize: args.normalize_lines,
+            top: args.top_lines,
+            approximate: args.approx,
+        };
+        report.duplicates = Some(dedup::analyze_files(&analyzed, &options, region)?);
+    }
+    if let Some(format) = args.timestamps.filter(|_| !incomplete) {
+        let options = timeline::TimelineOptions {
+            format,
+            bucket: args.bucket,
+        };
+        report.timeline = Some(timeline::analyze_files(&analyzed, &options, region)?);
+    }
+    report.violations = limits::check(&args.limit, &report.files);
+    // `freq` and `lint` read the files that could be counted once more. Like counting, a
+    // file that fails is reported and left out, and Ctrl-C stops between files.
+    let mut frequencies = None;
+    let mut problems = None;
+    let mut failed = |file: &Path, err: AnalyzerError| {
+        let err = anyhow::Error::from(err);
+        eprintln!("simple-wc-tool: {:#}", err);
+        report.errors.push(report::FileError::new(file, &err));
+    };
+    let remaining = || analyzed.iter().take_while(|_| !progress::interrupted());
+    match &mode {
+        Mode::Count => {}
+        _ if incomplete => {}
+        Mode::Freq(options) => {
+            let mut counter = freq::FreqCounter::new(options.clone());
+            for file in remaining() {
+                if let Err(err) = counter.add_file(file, region) {
+                    failed(file, err);
+                }
+            }
+            frequencies = Some(counter.finish());
+        }
+        Mode::Lint(options) => {
+            let mut issues = Vec::new();
+            for file in remaining() {
+                match lint::lint_file(file, options, region) {
+                    Ok(found) => issues.extend(found),
+                    Err(err) => failed(file, err),
+                }
+            }
+            problems = Some(issues);
+        }
+    }
+    let incomplete = progress::interrupted();
+    if incomplete && !report.incomplete {
+        eprintln!("Interrupted; results are incomplete");
+    }
+    report.incomplete = incomplete;
+
+    if let Some(path) = args.save_baseline.as_ref().filter(|_| !incomplete) {
+        report.save(path)?;
+    }
+
+    // Grouping sees every file; sorting and --top only shape what is listed.
+    if let Some(by) = args.group_by {
+        report.groups = group::group_files(&report.files, by, args.sort);
+    }
+    if args.tree {
+        report.tree = group::build_tree(&report.files, args.sort);
+    }
+    let sort = args.sort.or(args.top.map(|_| group::SortKey::Words));
+    if let Some(sort) = sort {
+        sort.sort_by(&mut report.files, |f| &f.path, |f| &f.stats);
+    }
+    // Compared before `--top` leaves files out, so they don't show up as removed.
+    let comparison = match &args.compare {
+        Some(path) => Some(compare::compare(&report::Report::load(path)?, &report)),
+        None => None,
+    };
+    if let Some(top) = args.top {
+        report.files.truncate(top);
+    }
+
+    if let Some(frequencies) = &frequencies {
+        match args.format {
+            Format::Text => print_frequencies(frequencies),
+            Format::Json => println!("{}", serde_json::to_string_pretty(frequencies)?),
+            Format::Csv => print_frequencies_csv(frequencies),
+        }
+    } else if let Some(problems) = &problems {
+        match args.format {
+            Format::Text => print_problems(problems, &report.violations, analyzed.len()),
+            Format::Json => {
+                let output = LintOutput {
+                    problems,
+                    violations: &report.violations,
+                };
+                println!("{}", serde_json::to_string_pretty(&output)?);
+            }
+            Format::Csv => print_problems_csv(problems, &report.violations),
+        }
+    } else if let Some(comparison) = &comparison {
+        match args.format {
+            Format::Text => compare::print_comparison(comparison),
+            Format::Json => println!("{}", serde_json::to_string_pretty(comparison)?),
+            Format::Csv => anyhow::bail!("comparisons have no CSV output"),
+        }
+    } else {
+        match args.format {
+            // Nothing to show when every file failed; the errors are already printed.
+            Format::Text if report.files.is_empty() && !report.errors.is_empty() => {}
+            Format::Text => print_report(&report, files.len() == 1 && !listed, &counting),
+            Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            Format::Csv => match &report.timeline {
+                Some(timeline) => print_timeline_csv(timeline),
+                None => print_csv(&report, &counting.columns),
+            },
+        }
+    }
+
+    if incomplete {
+        Ok(ExitCode::from(INTERRUPTED_EXIT_CODE))
+    } else if let Some(error) = report.errors.first() {
+        Ok(ExitCode::from(file_error_exit_code(error.kind)))
+    } else if report.violations.is_empty() && problems.is_none_or(|p| p.is_empty()) {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::from(LIMIT_VIOLATION_EXIT_CODE))
+    }
+}
+
+/// Without a subcommand, a first file named like one is most likely a subcommand after an
+/// option, as in `--no-config count FILE`, which clap takes as a file. Returns its name
+/// unless it came after `--`.
+fn misplaced_subcommand(files: &[PathBuf]) -> Option<String> {
+    let name = files.first()?.to_str()?;
+    Cli::command().find_subcommand(name)?;
+    let separated = std::env::args_os()
+        .skip(1)
+        .take_while(|arg| arg != name)
+        .any(|arg| arg == "--");
+    (!separated).then(|| name.to_string())
+}
+
+fn analyze_one(
+    file: &Path,
+    args: &Args,
+    counting: &Counting,
+    cache: Option<&mut cache::Cache>,
+    on_chunk: &mut analyzer::OnChunk,
+) -> anyhow::Result<report::FileReport> {
+    if args.estimate {
+        let options = estimate::EstimateOptions {
+            budget: args.sample_budget,
+            ..Default::default()
+        };
+        let estimate = estimate::estimate_file(file, &options)?;
+        let mut metrics = BTreeMap::new();
+        if counting.columns.contains(&report::Column::Bytes) {
+            metrics.insert("bytes".to_string(), estimate.size as usize);
+        }
+        return Ok(report::FileReport {
+            path: file.display().to_string(),
+            stats: estimate.stats(),
+            metrics,
+            estimate: Some(estimate),
+            ..Default::default()
+        });
+    }
+
+    let markup = args
+        .markup
+        .and_then(|mode| markup::Markup::for_path(file, mode));
+    let analysis = match cache {
+        Some(cache) => analyze_cached(cache, file, args.cache_hash, counting, markup, on_chunk)?,
+        None => counting.analyze(file, markup, on_chunk)?,
+    };
+    // After Ctrl-C, the table isn't read again.
+    let delimited = args.delimited.filter(|_| !progress::interrupted());
+    let table = match delimited.and_then(|mode| mode.delimiter_for(file)) {
+        Some(delimiter) => Some(delimited::analyze_file(
+            file,
+            delimiter,
+            !args.no_header,
+            counting.region.as_ref(),
+        )?),
+        None => None,
+    };
+    Ok(report::FileReport {
+        path: file.display().to_string(),
+        stats: analysis.stats,
+        code: analysis.code,
+        table,
+        metrics: analysis.metrics,
+        normalized: analysis.normalized,
+        language: analysis.language,
+        entities: analysis.entities,
+        estimate: None,
+        partial: false,
+    })
+}
+
+/// Options that change what is counted in every file.
+struct Counting {
+    region: Option<region::Region>,
+    normalization: Option<Normalization>,
+    detect_language: bool,
+    /// With `--entities`, how many of the most common values of each kind to list.
+    entities: Option<usize>,
+    /// The `--tokens` vocabulary, with a hash of the file for cache keys.
+    vocabulary: Option<(Arc<tokens::Vocabulary>, String)>,
+    columns: Vec<report::Column>,
+    /// Whether words and characters are needed, by the selected columns or anything
+    /// else that looks at them. Without words, plain files take the fast path.
+    needs_words: bool,
+    needs_chars: bool,
+}
+
+impl Counting {
+    fn new(args: &Args) -> anyhow::Result<Self> {
+        let vocabulary = match &args.tokens {
+            Some(path) => {
+                let vocabulary = tokens::Vocabulary::load(path)?;
+                let hash = vocabulary.hash().to_string();
+                Some((Arc::new(vocabulary), hash))
+            }
+            None => None,
+        };
+        // Grouping, trees and baselines show or store every count.
+        let all = args.group_by.is_some()
+            || args.tree
+            || args.compare.is_some()
+            || args.save_baseline.is_some();
+        let sort = args.sort.or(args.top.map(|_| group::SortKey::Words));
+        let needs = |column: report::Column, key: group::SortKey| {
+            all || args.metrics.contains(&column)
+                || args
+                    .limit
+                    .iter()
+                    .any(|limit| limit.metric() == column.name())
+                || sort == Some(key)
+        };
+        Ok(Counting {
+            region: args.input.region(),
+            normalization: args.normalize,
+            detect_language: args.detect_language,
+            entities: args.entities.then_some(args.top_entities),
+            vocabulary,
+            columns: args.metrics.clone(),
+            needs_words: needs(report::Column::Words, group::SortKey::Words),
+            needs_chars: needs(report::Column::Chars, group::SortKey::Chars),
+        })
+    }
+
+    /// Whether `fast` can count a file: nothing needs words or looks at the text itself.
+    fn is_fast(&self, markup: Option<markup::Markup>) -> bool {
+        !self.needs_words
+            && markup.is_none()
+            && self.normalization.is_none()
+            && !self.detect_language
+            && self.entities.is_none()
+            && self.vocabulary.is_none()
+    }
+
+    fn analyze(
+        &self,
+        file: &Path,
+        markup: Option<markup::Markup>,
+        on_chunk: &mut analyzer::OnChunk,
+    ) -> Result<analyzer::Analysis, AnalyzerError> {
+        let region = self.region.as_ref();
+        if !self.is_fast(markup) {
+            let analyzer = self.analyzer(markup);
+            return analyzer::analyze_file_with_progress(file, analyzer, region, on_chunk);
+        }
+
+        let counts = fast::count_file_with_progress(file, self.needs_chars, region, on_chunk)?;
+        let mut metrics = BTreeMap::new();
+        if self.columns.contains(&report::Column::Bytes) {
+            metrics.insert("bytes".to_string(), counts.bytes);
+        }
+        Ok(analyzer::Analysis {
+            stats: analyzer::FileStats {
+                words: 0,
+                lines: counts.lines,
+                chars: counts.chars,
+            },
+            code: None,
+            metrics,
+            normalized: None,
+            language: None,
+            entities: None,
+        })
+    }
+
+    fn analyzer(&self, markup: Option<markup::Markup>) -> analyzer::Analyzer {
+        let mut analyzer = analyzer::Analyzer::with_markup(markup);
+        if let Some(form) = self.normalization {
+            analyzer.set_normalization(form);
+        }
+        if self.detect_language {
+            analyzer.detect_language();
+        }
+        if self.entities.is_some() {
+            analyzer.count_entities();
+        }
+        if let Some((vocabulary, _)) = &self.vocabulary {
+            analyzer.add_metric(tokens::Tokens::new(Arc::clone(vocabulary)));
+        }
+        if self.columns.contains(&report::Column::Bytes) {
+            analyzer.add_metric(metric::Bytes::default());
+        }
+        analyzer
+    }
+
+    /// Identifies the options in the stats cache, so results are only reused for the same ones.
+    fn cache_settings(&self, markup: Option<markup::Markup>) -> String {
+        let mut settings = Vec::new();
+        if let Some(markup) = markup {
+            settings.push(format!("markup={:?}", markup).to_lowercase());
+        }
+        if let Some(region) = &self.region {
+            settings.push(region.to_string());
+        }
+        if let Some(form) = self.normalization {
+            settings.push(format!("normalize={}", form.name()));
+        }
+        if self.detect_language {
+            settings.push("language".to_string());
+        }
+        if self.entities.is_some() {
+            settings.push("entities".to_string());
+        }
+        if let Some((_, hash)) = &self.vocabulary {
+            settings.push(format!("tokens={}", hash));
+        }
+        if self.columns != report::Column::DEFAULT {
+            let names: Vec<&str> = self.columns.iter().map(|column| column.name()).collect();
+            settings.push(format!("metrics={}", names.join(",")));
+        }
+        // The fast path leaves out words, and characters unless they are needed.
+        if self.is_fast(markup) {
+            let counts = if self.needs_chars {
+                "lines,chars"
+            } else {
+                "lines"
+            };
+            settings.push(format!("counts={}", counts));
+        }
+        settings.join("\n")
+    }
+}
+
+fn analyze_cached(
+    cache: &mut cache::Cache,
+    file: &Path,
+    with_hash: bool,
+    counting: &Counting,
+    markup: Option<markup::Markup>,
+    on_chunk: &mut analyzer::OnChunk,
+) -> anyhow::Result<analyzer::Analysis> {
+    let settings = counting.cache_settings(markup);
+
+    // Taken before reading, so a change during analysis invalidates the entry on the next run.
+    // An unreadable file fails the same way as without the cache.
+    let fingerprint = cache::Fingerprint::of(file, with_hash).map_err(|e| match e
+        .downcast::<std::io::Error>()
+    {
+        Ok(e) => AnalyzerError::io(Some(file), e).into(),
+        Err(e) => e,
+    })?;
+    if let Some(analysis) = cache.get(file, &fingerprint, &settings) {
+        return Ok(analysis);
+    }
+
+    // A file that was only partly read isn't cached.
+    let mut stopped = false;
+    let mut on_chunk = |bytes| {
+        let flow = on_chunk(bytes);
+        stopped |= flow.is_break();
+        flow
+    };
+    let analysis = counting.analyze(file, markup, &mut on_chunk)?;
+    if !stopped {
+        cache.insert(file, fingerprint, &settings, analysis.clone());
+    }
+    Ok(analysis)
+}
+
+/// Prints the text report. `single_file` keeps the original three-line output
+/// for the plain one-file invocation.
+fn print_report(report: &report::Report, single_file: bool, counting: &Counting) {
+    let columns = &counting.columns;
+    let total = if report.incomplete {
+        "total (incomplete)"
+    } else {
+        "total"
+    };
+    if !report.groups.is_empty() || !report.tree.is_empty() {
+        if !report.groups.is_empty() {
+            group::print_groups(&report.groups);
+        }
+        if !report.tree.is_empty() {
+            group::print_tree(&report.tree);
+        }
+        println!("{}", report::stats_row(&report.total, total));
+    } else if let (true, [file]) = (single_file, report.files.as_slice()) {
+        for column in columns {
+            let value = column.value(&file.stats, &file.metrics);
+            match file.estimate.and_then(|e| column.interval(&e)) {
+                Some(interval) => println!(
+                    "{}: ~{} ({}..{} with 95% confidence)",
+                    column.title(),
+                    value,
+                    interval.low(),
+                    interval.high()
+                ),
+                None => println!("{}: {}", column.title(), value),
+            }
+        }
+        if let Some(tokens) = file.metrics.get("tokens") {
+            println!("Tokens: {}", tokens);
+        }
+        if let Some(code) = &file.code {
+            println!("Code words: {}", code.words);
+            println!("Code lines: {}", code.lines);
+            println!("Code characters: {}", code.chars);
+        }
+    } else {
+        // Additional metrics get a column each, named after the metric.
+        let names: Vec<&String> = report
+            .metrics
+            .keys()
+            .filter(|name| !columns.iter().any(|column| column.name() == name.as_str()))
+            .collect();
+        let header: Vec<String> = names.iter().map(|name| capitalize(name)).collect();
+        let row = |stats: &analyzer::FileStats,
+                   metrics: &BTreeMap<String, usize>,
+                   estimate: Option<Estimate>,
+                   name: &str| {
+            // Estimated counts are marked with a `~`.
+            let values: Vec<String> = columns
+                .iter()
+                .map(|column| {
+                    let value = column.value(stats, metrics);
+                    match estimate.and_then(|e| column.interval(&e)) {
+                        Some(_) => format!("~{}", value),
+                        None => value.to_string(),
+                    }
+                })
+                .collect();
+            let extra: Vec<String> = names
+                .iter()
+                .map(|metric| {
+                    metrics
+                        .get(*metric)
+                        .copied()
+                        .unwrap_or_default()
+                        .to_string()
+                })
+                .collect();
+            report::format_columns(columns, &values, &extra, name)
+        };
+
+        let titles: Vec<String> = columns.iter().map(|c| c.title().to_string()).collect();
+        println!(
+            "{}",
+            report::format_columns(columns, &titles, &header, "File")
+        );
+        for file in &report.files {
+            println!(
+                "{}",
+                row(&file.stats, &file.metrics, file.estimate, &file.path)
+            );
+        }
+        println!(
+            "{}",
+            row(&report.total, &report.metrics, report.estimate, total)
+        );
+        if let Some(estimate) = report.estimate.filter(|e| !e.exact) {
+            println!(
+                "Total with 95% confidence: words {}..{}, lines {}..{}, characters {}..{}",
+                estimate.words.low(),
+                estimate.words.high(),
+                estimate.lines.low(),
+                estimate.lines.high(),
+                estimate.chars.low(),
+                estimate.chars.high()
+            );
+        }
+    }
+
+    if let Some(estimate) = report.estimate.filter(|e| !e.exact) {
+        println!(
+            "Estimated from {} of {} bytes",
+            estimate.sampled_bytes, estimate.size
+        );
+    }
+
+    for file in &report.files {
+        if let Some(table) = &file.table {
+            if !single_file {
+                println!("\n{}:", file.path);
+            }
+            delimited::print_table_stats(table);
+        }
+    }
+
+    if let Some(form) = counting.normalization {
+        print_normalization(report, form, single_file);
+    }
+
+    if counting.detect_language {
+        print_languages(report, single_file);
+    }
+
+    if let (Some(top), Some(entities)) = (counting.entities, &report.entities) {
+        print_entities(entities, top);
+    }
+
+    if let Some(duplicates) = &report.duplicates {
+        print_dup_stats(duplicates);
+    }
+
+    if let Some(timeline) = &report.timeline {
+        print_timeline(timeline);
+    }
+
+    limits::print_violations(&report.violations);
+}
+
+fn print_timeline(timeline: &timeline::Timeline) {
+    let Some(format) = timeline.format else {
+        println!("No timestamps found");
+        return;
+    };
+    println!(
+        "Lines per {}, from {} timestamps (UTC):",
+        format_seconds(timeline.bucket_seconds),
+        format.name()
+    );
+    let row = |bucket: &timeline::Bucket| {
+        format!(
+            "{:<22}{:>10}{:>12}",
+            bucket.start, bucket.lines, bucket.bytes
+        )
+    };
+    println!("{:<22}{:>10}{:>12}", "Time", "Lines", "Bytes");
+    for bucket in &timeline.buckets {
+        println!("{}", row(bucket));
+    }
+    println!("Peaks:");
+    for bucket in &timeline.peaks {
+        println!("{}", row(bucket));
+    }
+    if !timeline.gaps.is_empty() {
+        println!("Gaps:");
+        for gap in &timeline.gaps {
+            println!(
+                "  {} to {} ({})",
+                gap.start,
+                gap.end,
+                format_seconds(gap.seconds)
+            );
+        }
+    }
+    if timeline.untimed_lines > 0 {
+        println!(
+            "Lines before the first timestamp: {}",
+            timeline.untimed_lines
+        );
+    }
+}
+
+/// `90` as `1m30s`, `3600` as `1h`.
+fn format_seconds(seconds: u64) -> String {
+    let units = [("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)];
+    let mut rest = seconds;
+    let mut text = String::new();
+    for (unit, size) in units {
+        if rest >= size {
+            text.push_str(&format!("{}{}", rest / size, unit));
+            rest %= size;
+        }
+    }
+    if text.is_empty() {
+        text.push_str("0s");
+    }
+    text
+}
+
+fn print_timeline_csv(timeline: &timeline::Timeline) {
+    println!("start,lines,bytes");
+    for bucket in &timeline.buckets {
+        println!("{},{},{}", bucket.start, bucket.lines, bucket.bytes);
+    }
+}
+
+fn print_csv(report: &report::Report, columns: &[report::Column]) {
+    let names: Vec<&str> = columns.iter().map(|column| column.name()).collect();
+    println!("file,{}", names.join(","));
+    for file in &report.files {
+        let values: Vec<String> = columns
+            .iter()
+            .map(|column| column.value(&file.stats, &file.metrics).to_string())
+            .collect();
+        println!("{},{}", csv_field(&file.path), values.join(","));
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn print_frequencies(frequencies: &freq::Frequencies) {
+    println!(
+        "{} words, {} distinct",
+        frequencies.words, frequencies.distinct
+    );
+    for (word, count) in &frequencies.top {
+        println!("{:>10}  {}", count, word);
+    }
+}
+
+fn print_frequencies_csv(frequencies: &freq::Frequencies) {
+    println!("word,count");
+    for (word, count) in &frequencies.top {
+        println!("{},{}", csv_field(word), count);
+    }
+}
+
+#[derive(serde::Serialize)]
+struct LintOutput<'a> {
+    problems: &'a [lint::Issue],
+    violations: &'a [limits::Violation],
+}
+
+/// Prints problems like compiler diagnostics, `path:line: message`, with a summary.
+fn print_problems(problems: &[lint::Issue], violations: &[limits::Violation], files: usize) {
+    for problem in problems {
+        println!(
+            "{}:{}: {} [{}]",
+            problem.path,
+            problem.line,
+            problem.message,
+            problem.check.name()
+        );
+    }
+    for violation in violations {
+        println!(
+            "{}: {} is {} (limit {})",
+            violation.path, violation.metric, violation.actual, violation.limit
+        );
+    }
+
+    let count = problems.len() + violations.len();
+    let files_noun = |n: usize| if n == 1 { "file" } else { "files" };
+    if count == 0 {
+        println!("No problems in {} {}", files, files_noun(files));
+    } else {
+        let paths: std::collections::BTreeSet<&str> = problems
+            .iter()
+            .map(|p| p.path.as_str())
+            .chain(violations.iter().map(|v| v.path.as_str()))
+            .collect();
+        let noun = if count == 1 { "problem" } else { "problems" };
+        println!(
+            "{} {} in {} {}",
+            count,
+            noun,
+            paths.len(),
+            files_noun(paths.len())
+        );
+    }
+}
+
+fn print_problems_csv(problems: &[lint::Issue], violations: &[limits::Violation]) {
+    println!("file,line,check,message");
+    for problem in problems {
+        println!(
+            "{},{},{},{}",
+            csv_field(&problem.path),
+            problem.line,
+            problem.check.name(),
+            csv_field(&problem.message)
+        );
+    }
+    for violation in violations {
+        let message = format!(
+            "{} is {} (limit {})",
+            violation.metric, violation.actual, violation.limit
+        );
+        println!(
+            "{},,limit,{}",
+            csv_field(&violation.path),
+            csv_field(&message)
+        );
+    }
+}
+
+fn print_entities(entities: &Entities, top: usize) {
+    println!("Entities:");
+    for (kind, count) in &entities.0 {
+        println!("  {}: {}", kind.title(), count.count);
+        for (value, n) in count.top.iter().take(top) {
+            println!("{:>10}  {}", n, value);
+        }
+    }
+}
+
+fn print_languages(report: &report::Report, single_file: bool) {
+    let describe = |file: &report::FileReport| match &file.language {
+        Some(detection) => format!(
+            "{} ({}), confidence {:.2}",
+            language::name(&detection.language),
+            detection.language,
+            detection.confidence
+        ),
+        None => "unknown".to_string(),
+    };
+    if let (true, [file]) = (single_file, report.files.as_slice()) {
+        println!("Language: {}", describe(file));
+        return;
+    }
+    println!("Languages:");
+    for file in &report.files {
+        println!("  {}: {}", file.path, describe(file));
+    }
+}
+
+fn print_normalization(report: &report::Report, form: Normalization, single_file: bool) {
+    let changed: Vec<&str> = report
+        .files
+        .iter()
+        .filter(|file| file.normalized == Some(false))
+        .map(|file| file.path.as_str())
+        .collect();
+    if single_file {
+        let state = if changed.is_empty() { "yes" } else { "no" };
+        println!("Already {}: {}", form.name(), state);
+    } else if changed.is_empty() {
+        println!("All files already {}", form.name());
+    } else {
+        println!("Not already {}:", form.name());
+        for path in changed {
+            println!("  {}", path);
+        }
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn print_dup_stats(stats: &dedup::DupStats) {
+    if stats.approximate {
+        println!("Distinct lines (approx.): {}", stats.distinct);
+        return;
+    }
+
+    println!("Distinct lines: {}", stats.distinct);
+    if let Some(unique) = stats.unique {
+        println!("Unique lines: {}", unique);
+    }
+    if let Some(duplicated) = stats.duplicated {
+        println!("Duplicated lines: {}", duplicated);
+    }
+    if !stats.top.is_empty() {
+        println!("Most repeated lines:");
+        for (line, count) in &stats.top {
+            println!("{:>8}  {}", count, line);
         }
-        Err(e) => Err(e),
     }
 }
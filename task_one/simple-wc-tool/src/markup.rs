@@ -0,0 +1,444 @@
+use clap::ValueEnum;
+use regex::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum MarkupMode {
+    Markdown,
+    Html,
+    /// Pick by file extension; other files are counted as plain text
+    Auto,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    Markdown,
+    Html,
+}
+
+impl Markup {
+    /// Resolves the mode for one file. Returns `None` when the file should be counted as is.
+    pub fn for_path(path: &Path, mode: MarkupMode) -> Option<Markup> {
+        match mode {
+            MarkupMode::Markdown => Some(Markup::Markdown),
+            MarkupMode::Html => Some(Markup::Html),
+            MarkupMode::Auto => {
+                let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+                match ext.as_str() {
+                    "md" | "markdown" | "mdown" | "mkd" => Some(Markup::Markdown),
+                    "html" | "htm" | "xhtml" => Some(Markup::Html),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+/// What is left of one source line once markup is removed.
+#[derive(Debug, Default, PartialEq)]
+pub struct Visible {
+    pub prose: Option<String>,
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HtmlState {
+    Text,
+    /// Inside `<...>`, with the opening quote of an attribute value if any.
+    Tag(Option<char>),
+    Comment,
+    /// Inside `<script>` or `<style>`, whose content is never visible.
+    Hidden,
+}
+
+/// Strips markup line by line. State carries across lines, so fenced code blocks,
+/// HTML comments and tags spanning several lines are handled.
+#[derive(Debug, Clone)]
+pub struct MarkupFilter {
+    markup: Markup,
+    /// Character and length of the open code fence.
+    fence: Option<(char, usize)>,
+    html: HtmlState,
+    tag: String,
+    hidden_until: Option<&'static str>,
+    pre_depth: usize,
+}
+
+impl MarkupFilter {
+    pub fn new(markup: Markup) -> Self {
+        MarkupFilter {
+            markup,
+            fence: None,
+            html: HtmlState::Text,
+            tag: String::new(),
+            hidden_until: None,
+            pre_depth: 0,
+        }
+    }
+
+    pub fn markup(&self) -> Markup {
+        self.markup
+    }
+
+    /// Blank lines stay blank prose lines; lines that only held markup disappear.
+    pub fn filter(&mut self, line: &str) -> Visible {
+        match self.markup {
+            Markup::Markdown => self.markdown_line(line),
+            Markup::Html => {
+                let (prose, code) = self.html_text(line);
+                Visible {
+                    prose: keep(line, prose),
+                    code: (!code.is_empty()).then_some(code),
+                }
+            }
+        }
+    }
+
+    fn markdown_line(&mut self, line: &str) -> Visible {
+        let trimmed = line.trim_start();
+
+        if let Some((marker, len)) = self.fence {
+            let closing = trimmed.trim_end();
+            if closing.len() >= len && closing.chars().all(|c| c == marker) {
+                self.fence = None;
+                return Visible::default();
+            }
+            return Visible {
+                prose: None,
+                code: Some(line.to_string()),
+            };
+        }
+        if let Some(fence) = fence_marker(trimmed) {
+            self.fence = Some(fence);
+            return Visible::default();
+        }
+        if self.html != HtmlState::Text || trimmed.starts_with('<') {
+            let (prose, _) = self.html_text(line);
+            self.end_markdown_line();
+            return Visible {
+                prose: keep(line, prose),
+                code: None,
+            };
+        }
+        if is_thematic_break(trimmed) || reference_definition().is_match(trimmed) {
+            return Visible::default();
+        }
+        if table_separator().is_match(trimmed) {
+            return Visible::default();
+        }
+
+        let (prose, _) = self.html_text(&strip_markdown_inline(strip_block_prefix(line)));
+        self.end_markdown_line();
+        Visible {
+            prose: keep(line, prose),
+            code: None,
+        }
+    }
+
+    /// A `<` in Markdown prose is often just a less-than sign, so an unterminated
+    /// tag doesn't swallow the following lines. Comments may still span lines.
+    fn end_markdown_line(&mut self) {
+        if let HtmlState::Tag(_) = self.html {
+            self.html = HtmlState::Text;
+        }
+    }
+
+    /// Removes tags and comments, decodes entities and splits `<pre>` content off as code.
+    fn html_text(&mut self, line: &str) -> (String, String) {
+        let mut prose = String::new();
+        let mut code = String::new();
+        let mut chars = line.char_indices();
+
+        while let Some((i, c)) = chars.next() {
+            match self.html {
+                HtmlState::Text => {
+                    if c == '<' {
+                        if line[i..].starts_with("<!--") {
+                            self.html = HtmlState::Comment;
+                            chars.nth(2);
+                        } else if line[i + 1..]
+                            .starts_with(|n: char| n.is_ascii_alphabetic() || n == '/' || n == '!')
+                        {
+                            self.html = HtmlState::Tag(None);
+                            self.tag.clear();
+                        } else {
+                            self.push_text(&mut prose, &mut code, c);
+                        }
+                    } else if c == '&' {
+                        match decode_entity(&line[i..]) {
+                            Some((decoded, len)) => {
+                                self.push_text(&mut prose, &mut code, decoded);
+                                for _ in 1..len {
+                                    chars.next();
+                                }
+                            }
+                            None => self.push_text(&mut prose, &mut code, c),
+                        }
+                    } else {
+                        self.push_text(&mut prose, &mut code, c);
+                    }
+                }
+                HtmlState::Tag(quote) => match (quote, c) {
+                    (None, '>') => {
+                        self.html = HtmlState::Text;
+                        self.close_tag(&mut prose);
+                    }
+                    (None, '"' | '\'') => self.html = HtmlState::Tag(Some(c)),
+                    (Some(q), _) if c == q => self.html = HtmlState::Tag(None),
+                    _ => self.tag.push(c),
+                },
+                HtmlState::Comment => {
+                    if line[i..].starts_with("-->") {
+                        self.html = HtmlState::Text;
+                        chars.nth(1);
+                    }
+                }
+                HtmlState::Hidden => {
+                    let end = self.hidden_until.unwrap_or("");
+                    let at_end = line
+                        .get(i..i + end.len())
+                        .is_some_and(|s| s.eq_ignore_ascii_case(end));
+                    if at_end {
+                        self.html = HtmlState::Tag(None);
+                        self.tag = end[1..].to_string();
+                        for _ in 1..end.len() {
+                            chars.next();
+                        }
+                    }
+                }
+            }
+        }
+
+        (prose, code)
+    }
+
+    fn push_text(&self, prose: &mut String, code: &mut String, c: char) {
+        if self.pre_depth > 0 {
+            code.push(c);
+        } else {
+            prose.push(c);
+        }
+    }
+
+    /// Reacts to the tag that just ended. Tags separate words, so a space is left behind.
+    fn close_tag(&mut self, prose: &mut String) {
+        let tag = self.tag.trim().to_ascii_lowercase();
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+        let closing = tag.starts_with('/');
+
+        match (name.as_str(), closing) {
+            ("script", false) if !tag.ends_with('/') => {
+                self.html = HtmlState::Hidden;
+                self.hidden_until = Some("</script");
+            }
+            ("style", false) => {
+                self.html = HtmlState::Hidden;
+                self.hidden_until = Some("</style");
+            }
+            ("pre", false) => self.pre_depth += 1,
+            ("pre", true) => self.pre_depth = self.pre_depth.saturating_sub(1),
+            _ => {}
+        }
+
+        if self.pre_depth == 0 && !prose.ends_with(' ') && !prose.is_empty() {
+            prose.push(' ');
+        }
+    }
+}
+
+/// A line survives if it was blank to begin with or still has visible text.
+fn keep(original: &str, visible: String) -> Option<String> {
+    if original.trim().is_empty() {
+        Some(String::new())
+    } else if visible.trim().is_empty() {
+        None
+    } else {
+        Some(visible.trim().to_string())
+    }
+}
+
+fn fence_marker(trimmed: &str) -> Option<(char, usize)> {
+    ['`', '~'].into_iter().find_map(|marker| {
+        let count = trimmed.chars().take_while(|&c| c == marker).count();
+        (count >= 3).then_some((marker, count))
+    })
+}
+
+fn is_thematic_break(trimmed: &str) -> bool {
+    let compact: String = trimmed.chars().filter(|c| !c.is_whitespace()).collect();
+    compact.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&m| compact.chars().all(|c| c == m))
+}
+
+/// Drops heading, blockquote and list markers.
+fn strip_block_prefix(line: &str) -> &str {
+    let mut rest = line.trim_start();
+    loop {
+        let before = rest;
+        rest = rest.trim_start_matches('>').trim_start();
+        if let Some(heading) = heading_prefix().find(rest) {
+            rest = &rest[heading.end()..];
+        }
+        if let Some(item) = list_prefix().find(rest) {
+            rest = &rest[item.end()..];
+        }
+        if rest == before {
+            return rest;
+        }
+    }
+}
+
+fn strip_markdown_inline(text: &str) -> String {
+    let text = image_or_link().replace_all(text, "$text");
+    let text = autolink().replace_all(&text, "$url");
+    let text = emphasis().replace_all(&text, "$inner");
+    let text = text.replace('`', "").replace('|', " ");
+    text.trim_end_matches('#').to_string()
+}
+
+fn decode_entity(text: &str) -> Option<(char, usize)> {
+    let end = text.find(';')?;
+    if end > 10 {
+        return None;
+    }
+    let name = &text[1..end];
+    let decoded = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let value = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(value)?
+        }
+    };
+    Some((decoded, text[..=end].chars().count()))
+}
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("markup pattern is valid"))
+}
+
+fn reference_definition() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    regex(&RE, r"^\[[^\]]+\]:\s*\S+")
+}
+
+fn table_separator() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    regex(&RE, r"^\|?\s*:?-+:?\s*(\|\s*:?-+:?\s*)*\|?\s*$")
+}
+
+fn heading_prefix() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    regex(&RE, r"^#{1,6}(\s+|$)")
+}
+
+fn list_prefix() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    regex(&RE, r"^([-*+]|\d{1,9}[.)])\s+(\[[ xX]\]\s+)?")
+}
+
+fn image_or_link() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    regex(&RE, r"!?\[(?P<text>[^\]]*)\](\([^)]*\)|\[[^\]]*\])")
+}
+
+fn autolink() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    regex(&RE, r"<(?P<url>[a-zA-Z][a-zA-Z0-9+.-]*:[^\s>]+)>")
+}
+
+fn emphasis() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    regex(
+        &RE,
+        r"(\*\*\*|\*\*|\*|~~|\b___|\b__|\b_)(?P<inner>\S(?:.*?\S)?)(\*\*\*|\*\*|\*|~~|___\b|__\b|_\b)",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prose(markup: Markup, text: &str) -> Vec<String> {
+        let mut filter = MarkupFilter::new(markup);
+        text.lines()
+            .filter_map(|line| filter.filter(line).prose)
+            .collect()
+    }
+
+    fn code(markup: Markup, text: &str) -> Vec<String> {
+        let mut filter = MarkupFilter::new(markup);
+        text.lines()
+            .filter_map(|line| filter.filter(line).code)
+            .collect()
+    }
+
+    #[test]
+    fn test_markdown_inline_markup() {
+        let text = "# Title\n\nSee [the docs](https://example.com/docs) and **bold** `code`.\n- item one\n> quoted";
+        assert_eq!(
+            prose(Markup::Markdown, text),
+            vec![
+                "Title",
+                "",
+                "See the docs and bold code.",
+                "item one",
+                "quoted"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markdown_code_blocks_are_separate() {
+        let text = "Intro\n```rust\nfn main() {}\n```\nOutro\n~~~~\nplain\n~~~~";
+        assert_eq!(prose(Markup::Markdown, text), vec!["Intro", "Outro"]);
+        assert_eq!(code(Markup::Markdown, text), vec!["fn main() {}", "plain"]);
+    }
+
+    #[test]
+    fn test_markdown_structure_lines_are_dropped() {
+        let text = "| a | b |\n|---|:-:|\n| 1 | 2 |\n---\n[id]: https://example.com\n<!-- note -->\nsnake_case stays";
+        assert_eq!(
+            prose(Markup::Markdown, text),
+            vec!["a   b", "1   2", "snake_case stays"]
+        );
+    }
+
+    #[test]
+    fn test_html() {
+        let text = "<html><head><style>p { color: red; }</style>\n<script>\nvar x = 1;\n</script></head>\n<body><p>Hello &amp; <a href=\"a>b\">welcome</a></p>\n<!-- multi\nline -->\n<pre>let x = 1;</pre>\n</body>";
+        assert_eq!(prose(Markup::Html, text), vec!["Hello & welcome"]);
+        assert_eq!(code(Markup::Html, text), vec!["let x = 1;"]);
+    }
+
+    #[test]
+    fn test_auto_mode() {
+        assert_eq!(
+            Markup::for_path(Path::new("README.md"), MarkupMode::Auto),
+            Some(Markup::Markdown)
+        );
+        assert_eq!(
+            Markup::for_path(Path::new("index.HTML"), MarkupMode::Auto),
+            Some(Markup::Html)
+        );
+        assert_eq!(
+            Markup::for_path(Path::new("notes.txt"), MarkupMode::Auto),
+            None
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use crate::analyzer::count_words;
+
+/// Something computed over the input, fed by an [`Analyzer`](crate::analyzer::Analyzer).
+///
+/// Every method but [`value`](Metric::value) has an empty default, so a metric only
+/// implements the inputs it needs:
+///
+/// ```
+/// use simple_wc_tool::analyzer::Analyzer;
+/// use simple_wc_tool::metric::Metric;
+///
+/// #[derive(Default)]
+/// struct LongestLine(usize);
+///
+/// impl Metric for LongestLine {
+///     fn name(&self) -> &str {
+///         "longest-line"
+///     }
+///
+///     fn line(&mut self, line: &str) {
+///         self.0 = self.0.max(line.chars().count());
+///     }
+///
+///     fn value(&self) -> usize {
+///         self.0
+///     }
+/// }
+///
+/// let mut analyzer = Analyzer::new();
+/// analyzer.add_metric(LongestLine::default());
+/// analyzer.update(b"short\nmuch longer\n").unwrap();
+/// let analysis = analyzer.finish().unwrap();
+/// assert_eq!(analysis.metrics["longest-line"], 11);
+/// ```
+pub trait Metric: Send {
+    /// Name the value is reported under.
+    fn name(&self) -> &str;
+
+    /// Raw input, in the chunks it arrives in, before markup is stripped.
+    fn chunk(&mut self, _chunk: &[u8]) {}
+
+    /// Characters of the counted text, in pieces: the content of each line, and a
+    /// `"\n"` between two lines (but not after the last one).
+    fn chars(&mut self, _text: &str) {}
+
+    /// Each line, without its line ending. Called after the line's characters.
+    fn line(&mut self, _line: &str) {}
+
+    /// Starts over, as if nothing had been fed, e.g. after the input was truncated.
+    fn reset(&mut self) {}
+
+    fn value(&self) -> usize;
+}
+
+/// Feeds one line, preceded by its separator unless it is the first line.
+pub(crate) fn feed_line<M: Metric + ?Sized>(metric: &mut M, line: &str, first: bool) {
+    if !first {
+        metric.chars("\n");
+    }
+    metric.chars(line);
+    metric.line(line);
+}
+
+/// Whitespace-separated words.
+#[derive(Debug, Default, Clone)]
+pub struct Words(usize);
+
+impl Metric for Words {
+    fn name(&self) -> &str {
+        "words"
+    }
+
+    fn line(&mut self, line: &str) {
+        self.0 += count_words(line);
+    }
+
+    fn reset(&mut self) {
+        self.0 = 0;
+    }
+
+    fn value(&self) -> usize {
+        self.0
+    }
+}
+
+/// Lines; a trailing newline doesn't start another one.
+#[derive(Debug, Default, Clone)]
+pub struct Lines(usize);
+
+impl Metric for Lines {
+    fn name(&self) -> &str {
+        "lines"
+    }
+
+    fn line(&mut self, _line: &str) {
+        self.0 += 1;
+    }
+
+    fn reset(&mut self) {
+        self.0 = 0;
+    }
+
+    fn value(&self) -> usize {
+        self.0
+    }
+}
+
+/// Characters, counting each line separator as one.
+#[derive(Debug, Default, Clone)]
+pub struct Chars(usize);
+
+impl Metric for Chars {
+    fn name(&self) -> &str {
+        "chars"
+    }
+
+    fn chars(&mut self, text: &str) {
+        self.0 += text.chars().count();
+    }
+
+    fn reset(&mut self) {
+        self.0 = 0;
+    }
+
+    fn value(&self) -> usize {
+        self.0
+    }
+}
+
+/// Bytes of the raw input.
+#[derive(Debug, Default, Clone)]
+pub struct Bytes(usize);
+
+impl Metric for Bytes {
+    fn name(&self) -> &str {
+        "bytes"
+    }
+
+    fn chunk(&mut self, chunk: &[u8]) {
+        self.0 += chunk.len();
+    }
+
+    fn reset(&mut self) {
+        self.0 = 0;
+    }
+
+    fn value(&self) -> usize {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::Analyzer;
+
+    /// Records everything it is fed.
+    #[derive(Default)]
+    struct Text(String);
+
+    impl Metric for Text {
+        fn name(&self) -> &str {
+            "text"
+        }
+
+        fn chars(&mut self, text: &str) {
+            self.0.push_str(text);
+        }
+
+        fn value(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    #[test]
+    fn test_custom_metrics_see_the_counted_text() {
+        let mut text = Text::default();
+        for (i, line) in ["one", "two", ""].into_iter().enumerate() {
+            feed_line(&mut text, line, i == 0);
+        }
+        assert_eq!(text.0, "one\ntwo\n");
+
+        let mut analyzer = Analyzer::new();
+        analyzer.add_metric(Bytes::default());
+        analyzer.add_metric(Text::default());
+        analyzer.update("héllo\r\nwor".as_bytes()).unwrap();
+        analyzer.update(b"ld\n").unwrap();
+        let analysis = analyzer.finish().unwrap();
+
+        assert_eq!(analysis.metrics["bytes"], 14);
+        // "héllo\nworld", with é taking two bytes.
+        assert_eq!(analysis.metrics["text"], 12);
+        assert_eq!(analysis.stats.chars, 11);
+    }
+}
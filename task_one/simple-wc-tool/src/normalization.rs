@@ -0,0 +1,69 @@
+use clap::ValueEnum;
+use std::borrow::Cow;
+use unicode_normalization::UnicodeNormalization;
+
+/// A Unicode normalization form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Normalization {
+    /// Canonical composition, e.g. `é` as one code point
+    Nfc,
+    /// Canonical decomposition, e.g. `é` as `e` and a combining accent
+    Nfd,
+    /// Compatibility composition, which also folds e.g. `ﬁ` into `fi`
+    Nfkc,
+    /// Compatibility decomposition
+    Nfkd,
+}
+
+impl Normalization {
+    pub fn name(self) -> &'static str {
+        match self {
+            Normalization::Nfc => "NFC",
+            Normalization::Nfd => "NFD",
+            Normalization::Nfkc => "NFKC",
+            Normalization::Nfkd => "NFKD",
+        }
+    }
+
+    pub fn is_normalized(self, text: &str) -> bool {
+        match self {
+            Normalization::Nfc => unicode_normalization::is_nfc(text),
+            Normalization::Nfd => unicode_normalization::is_nfd(text),
+            Normalization::Nfkc => unicode_normalization::is_nfkc(text),
+            Normalization::Nfkd => unicode_normalization::is_nfkd(text),
+        }
+    }
+
+    /// Normalizes `text`, without copying it when it already is in this form.
+    pub fn apply(self, text: &str) -> Cow<'_, str> {
+        if self.is_normalized(text) {
+            return Cow::Borrowed(text);
+        }
+        Cow::Owned(match self {
+            Normalization::Nfc => text.nfc().collect(),
+            Normalization::Nfd => text.nfd().collect(),
+            Normalization::Nfkc => text.nfkc().collect(),
+            Normalization::Nfkd => text.nfkd().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forms() {
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+
+        assert_eq!(Normalization::Nfc.apply(decomposed), composed);
+        assert_eq!(Normalization::Nfd.apply(composed), decomposed);
+        assert!(matches!(
+            Normalization::Nfc.apply(composed),
+            Cow::Borrowed(_)
+        ));
+        assert!(!Normalization::Nfc.is_normalized(decomposed));
+        assert_eq!(Normalization::Nfkc.apply("\u{fb01}ne"), "fine");
+    }
+}
@@ -0,0 +1,156 @@
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Minimum time between two redraws of the progress line.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Makes the first Ctrl-C only set [`interrupted`], so the run can stop reading, even in
+/// the middle of a file, and still report what it has. A second one exits right away.
+pub fn handle_interrupts() -> anyhow::Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+    })?;
+    Ok(())
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// A one-line progress indicator on stderr: files and bytes done, throughput and ETA.
+/// Does nothing unless stderr is a terminal.
+pub struct Progress {
+    enabled: bool,
+    /// Sizes of the files, in the order they are analyzed.
+    sizes: Vec<u64>,
+    files: usize,
+    /// Bytes of the files done.
+    bytes: u64,
+    /// Bytes read of the current file.
+    current: u64,
+    total_bytes: u64,
+    started: Instant,
+    drawn: Option<Instant>,
+}
+
+impl Progress {
+    pub fn new(files: &[PathBuf]) -> Self {
+        let enabled = io::stderr().is_terminal();
+        let sizes: Vec<u64> = if enabled {
+            files
+                .iter()
+                .map(|file| fs::metadata(file).map_or(0, |metadata| metadata.len()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Progress {
+            enabled,
+            total_bytes: sizes.iter().sum(),
+            sizes,
+            files: 0,
+            bytes: 0,
+            current: 0,
+            started: Instant::now(),
+            drawn: None,
+        }
+    }
+
+    /// Records `bytes` more read of the current file.
+    pub fn read(&mut self, bytes: usize) {
+        self.current += bytes as u64;
+        self.draw();
+    }
+
+    /// Records that the current file is done.
+    pub fn file_done(&mut self) {
+        self.bytes += self.sizes.get(self.files).copied().unwrap_or_default();
+        self.current = 0;
+        self.files += 1;
+        self.draw();
+    }
+
+    fn draw(&mut self) {
+        if !self.enabled || self.drawn.is_some_and(|t| t.elapsed() < REDRAW_INTERVAL) {
+            return;
+        }
+        let line = status_line(
+            self.files,
+            self.sizes.len(),
+            self.bytes + self.current,
+            self.total_bytes,
+            self.started.elapsed(),
+        );
+        eprint!("\r{}\x1b[K", line);
+        let _ = io::stderr().flush();
+        self.drawn = Some(Instant::now());
+    }
+
+    /// Removes the progress line, e.g. before printing something else to stderr.
+    pub fn clear(&mut self) {
+        if self.drawn.take().is_some() {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+fn status_line(
+    files: usize,
+    total_files: usize,
+    bytes: u64,
+    total_bytes: u64,
+    elapsed: Duration,
+) -> String {
+    let rate = bytes as f64 / elapsed.as_secs_f64().max(0.001);
+    let mut line = format!(
+        "{}/{} files  {} / {}  {}/s",
+        files,
+        total_files,
+        format_bytes(bytes as f64),
+        format_bytes(total_bytes as f64),
+        format_bytes(rate)
+    );
+    if rate > 0.0 && total_bytes > bytes {
+        let eta = ((total_bytes - bytes) as f64 / rate).round() as u64;
+        line.push_str(&format!("  ETA {}:{:02}", eta / 60, eta % 60));
+    }
+    line
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", value as u64)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_line() {
+        assert_eq!(format_bytes(512.0), "512 B");
+        assert_eq!(format_bytes(1536.0 * 1024.0), "1.5 MiB");
+
+        let line = status_line(3, 10, 10 << 20, 40 << 20, Duration::from_secs(2));
+        assert_eq!(line, "3/10 files  10.0 MiB / 40.0 MiB  5.0 MiB/s  ETA 0:06");
+        let done = status_line(10, 10, 40 << 20, 40 << 20, Duration::from_secs(8));
+        assert!(!done.contains("ETA"));
+    }
+}
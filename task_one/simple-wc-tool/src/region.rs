@@ -0,0 +1,331 @@
+use crate::error::AnalyzerError;
+use regex::Regex;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::str::FromStr;
+
+/// A range written as `START..END`, where either side may be left out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+}
+
+impl FromStr for Range {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once("..")
+            .ok_or_else(|| format!("range '{}' must look like START..END", s))?;
+        let bound = |value: &str| -> Result<Option<u64>, String> {
+            match value.trim() {
+                "" => Ok(None),
+                value => value
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| format!("range '{}' needs whole numbers", s)),
+            }
+        };
+        let range = Range {
+            start: bound(start)?,
+            end: bound(end)?,
+        };
+        if let (Some(start), Some(end)) = (range.start, range.end) {
+            if start > end {
+                return Err(format!("range '{}' ends before it starts", s));
+            }
+        }
+        Ok(range)
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(start) = self.start {
+            write!(f, "{}", start)?;
+        }
+        write!(f, "..")?;
+        if let Some(end) = self.end {
+            write!(f, "{}", end)?;
+        }
+        Ok(())
+    }
+}
+
+/// The part of a file to analyze.
+#[derive(Debug, Clone)]
+pub enum Region {
+    /// Lines numbered from 1, both ends included.
+    Lines(Range),
+    /// Byte offsets from 0, end excluded. The range is widened to whole characters.
+    Bytes(Range),
+    /// Every line after a line matching the first regex, up to a line matching the second.
+    /// The marker lines themselves are not selected.
+    Between(Regex, Regex),
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Region::Lines(range) => write!(f, "lines={}", range),
+            Region::Bytes(range) => write!(f, "bytes={}", range),
+            Region::Between(start, end) => write!(f, "between={}\n{}", start, end),
+        }
+    }
+}
+
+/// Opens a file, reading only the selected region when there is one. A byte range
+/// starts with a seek when the file allows it, instead of reading up to the start.
+pub fn open(path: &Path, region: Option<&Region>) -> Result<RegionReader<File>, AnalyzerError> {
+    let mut file = File::open(path).map_err(|e| AnalyzerError::io(Some(path), e))?;
+    let mut reader_offset = 0;
+    if let Some(&Region::Bytes(Range {
+        start: Some(start), ..
+    })) = region
+    {
+        // Pipes can't seek; those are read from the beginning.
+        // The selector still skips a character the start cuts through.
+        if file.seek(SeekFrom::Start(start)).is_ok() {
+            reader_offset = start;
+        }
+    }
+    let mut reader = RegionReader::new(file, region.cloned());
+    if let Some(selector) = &mut reader.selector {
+        selector.offset = reader_offset;
+    }
+    Ok(reader)
+}
+
+/// Passes through only the selected region of the inner reader. Reading stops early
+/// once a line or byte range has been passed.
+pub struct RegionReader<R> {
+    inner: R,
+    selector: Option<Selector>,
+    buf: Vec<u8>,
+    out: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read> RegionReader<R> {
+    pub fn new(inner: R, region: Option<Region>) -> Self {
+        RegionReader {
+            inner,
+            selector: region.map(Selector::new),
+            buf: vec![0; 8192],
+            out: Vec::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Read for RegionReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(selector) = &mut self.selector else {
+            return self.inner.read(buf);
+        };
+
+        while self.pos == self.out.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.out.clear();
+            self.pos = 0;
+
+            let n = self.inner.read(&mut self.buf)?;
+            if n == 0 {
+                selector.finish(&mut self.out);
+                self.done = true;
+            } else {
+                selector.select(&self.buf[..n], &mut self.out);
+                self.done = selector.past_end;
+            }
+        }
+
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
+}
+
+struct Selector {
+    region: Region,
+    /// Offset of the next byte.
+    offset: u64,
+    /// Number of the current line.
+    line: u64,
+    /// The current line, buffered until it is complete for marker matching.
+    line_buf: Vec<u8>,
+    /// Whether anything was selected yet.
+    started: bool,
+    inside: bool,
+    past_end: bool,
+}
+
+impl Selector {
+    fn new(region: Region) -> Self {
+        Selector {
+            region,
+            offset: 0,
+            line: 1,
+            line_buf: Vec::new(),
+            started: false,
+            inside: false,
+            past_end: false,
+        }
+    }
+
+    fn select(&mut self, chunk: &[u8], out: &mut Vec<u8>) {
+        for &byte in chunk {
+            if self.past_end {
+                return;
+            }
+            match &self.region {
+                Region::Lines(range) => {
+                    let selected = range.start.is_none_or(|start| self.line >= start)
+                        && range.end.is_none_or(|end| self.line <= end);
+                    if selected {
+                        out.push(byte);
+                    }
+                    if byte == b'\n' {
+                        self.line += 1;
+                        self.past_end = range.end.is_some_and(|end| self.line > end);
+                    }
+                }
+                Region::Bytes(range) => {
+                    let after_start = range.start.is_none_or(|start| self.offset >= start);
+                    let before_end = range.end.is_none_or(|end| self.offset < end);
+                    if after_start && before_end {
+                        // Don't start in the middle of a character.
+                        if self.started || !is_continuation(byte) {
+                            self.started = true;
+                            out.push(byte);
+                        }
+                    } else if !before_end {
+                        // Finish the character the range ended in.
+                        if self.started && is_continuation(byte) {
+                            out.push(byte);
+                        } else {
+                            self.past_end = true;
+                        }
+                    }
+                    self.offset += 1;
+                }
+                Region::Between(..) => self.line_buf.push(byte),
+            }
+            if byte == b'\n' && !self.line_buf.is_empty() {
+                self.end_line(out);
+            }
+        }
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) {
+        if !self.line_buf.is_empty() {
+            self.end_line(out);
+        }
+    }
+
+    fn end_line(&mut self, out: &mut Vec<u8>) {
+        let Region::Between(start, end) = &self.region else {
+            return;
+        };
+        let line = std::mem::take(&mut self.line_buf);
+        let text = String::from_utf8_lossy(&line);
+
+        if !self.inside {
+            if let Some(found) = start.find(&text) {
+                // A region opened and closed on the same line selects nothing.
+                self.inside = !end.is_match(&text[found.end()..]);
+            }
+        } else if end.is_match(&text) {
+            self.inside = false;
+        } else {
+            out.extend_from_slice(&line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn select(input: &str, region: Region) -> String {
+        let mut reader = RegionReader::new(input.as_bytes(), Some(region));
+        let mut out = String::new();
+        reader.read_to_string(&mut out).unwrap();
+        out
+    }
+
+    fn range(s: &str) -> Range {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(
+            range("100..500"),
+            Range {
+                start: Some(100),
+                end: Some(500)
+            }
+        );
+        assert_eq!(range("..5").to_string(), "..5");
+        assert!("5".parse::<Range>().is_err());
+        assert!("5..x".parse::<Range>().is_err());
+        assert!("9..5".parse::<Range>().is_err());
+    }
+
+    #[test]
+    fn test_lines() {
+        let input = "one\ntwo\nthree\nfour\n";
+        assert_eq!(select(input, Region::Lines(range("2..3"))), "two\nthree\n");
+        assert_eq!(select(input, Region::Lines(range("4.."))), "four\n");
+        assert_eq!(select(input, Region::Lines(range("..1"))), "one\n");
+    }
+
+    #[test]
+    fn test_bytes_keep_whole_characters() {
+        assert_eq!(select("hello world", Region::Bytes(range("6.."))), "world");
+        // 'é' takes bytes 1 and 2; both ranges cut through it.
+        assert_eq!(select("héllo", Region::Bytes(range("0..2"))), "hé");
+        assert_eq!(select("héllo", Region::Bytes(range("2..5"))), "ll");
+    }
+
+    #[test]
+    fn test_bytes_seek_to_the_start() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all("héllo world".as_bytes()).unwrap();
+        let read = |range: &str| {
+            let mut reader = open(file.path(), Some(&Region::Bytes(self::range(range)))).unwrap();
+            let start = reader.inner.stream_position().unwrap();
+            let mut out = String::new();
+            reader.read_to_string(&mut out).unwrap();
+            (start, out)
+        };
+        assert_eq!(read("7.."), (7, "world".to_string()));
+        assert_eq!(read("2..6"), (2, "llo".to_string()));
+        assert_eq!(read("..3"), (0, "hé".to_string()));
+        assert_eq!(read("20.."), (20, String::new()));
+    }
+
+    #[test]
+    fn test_between_markers() {
+        let input = "intro\n<!-- start -->\nkept\nalso kept\n<!-- end -->\nskipped\n\
+                     <!-- start -->\nagain\n<!-- end -->\n<!-- start --> x <!-- end -->\nnot kept\n";
+        let region = Region::Between(
+            Regex::new("<!-- start -->").unwrap(),
+            Regex::new("<!-- end -->").unwrap(),
+        );
+        assert_eq!(select(input, region), "kept\nalso kept\nagain\n");
+    }
+}
@@ -0,0 +1,387 @@
+use crate::analyzer::FileStats;
+use crate::dedup::DupStats;
+use crate::delimited::TableStats;
+use crate::group::{Group, TreeNode};
+use crate::limits::Violation;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use simple_wc_tool::entities::Entities;
+use simple_wc_tool::error::AnalyzerError;
+use simple_wc_tool::estimate::{Estimate, Interval};
+use simple_wc_tool::language::Detection;
+use simple_wc_tool::timeline::Timeline;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Results of one run, as printed with `--format json` and stored as a baseline.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub files: Vec<FileReport>,
+    pub total: FileStats,
+    /// Totals of the additional metrics.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: BTreeMap<String, usize>,
+    /// Totals of the entity counts, with `--entities`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entities: Option<Entities>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicates: Option<DupStats>,
+    /// Lines and bytes over time, with `--timestamps`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeline: Option<Timeline>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<Violation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<Group>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tree: Vec<TreeNode>,
+    /// Files that couldn't be analyzed; they are left out of `files` and `total`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FileError>,
+    /// Totals of the estimated counts, with `--estimate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<Estimate>,
+    /// Whether the run was interrupted, so that `files` and `total` only cover part of the input.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub incomplete: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    NotFound,
+    PermissionDenied,
+    InvalidUtf8,
+    Io,
+    Other,
+}
+
+impl ErrorKind {
+    /// Classifies an error by the [`AnalyzerError`] or I/O error behind it.
+    pub fn of(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if let Some(err) = cause.downcast_ref::<AnalyzerError>() {
+                return match err {
+                    AnalyzerError::NotFound { .. } => ErrorKind::NotFound,
+                    AnalyzerError::PermissionDenied { .. } => ErrorKind::PermissionDenied,
+                    AnalyzerError::InvalidUtf8 { .. } => ErrorKind::InvalidUtf8,
+                    AnalyzerError::Io { .. } => ErrorKind::Io,
+                };
+            }
+            if let Some(err) = cause.downcast_ref::<io::Error>() {
+                return match err.kind() {
+                    io::ErrorKind::NotFound => ErrorKind::NotFound,
+                    io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+                    io::ErrorKind::InvalidData => ErrorKind::InvalidUtf8,
+                    _ => ErrorKind::Io,
+                };
+            }
+        }
+        ErrorKind::Other
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct FileError {
+    pub path: String,
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl FileError {
+    pub fn new(path: &Path, err: &anyhow::Error) -> Self {
+        FileError {
+            path: path.display().to_string(),
+            kind: ErrorKind::of(err),
+            message: format!("{:#}", err),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileReport {
+    pub path: String,
+    #[serde(flatten)]
+    pub stats: FileStats,
+    /// Counts of code blocks, kept apart from `stats` with `--markup`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<FileStats>,
+    /// Structural stats of CSV and TSV files, with `--delimited`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table: Option<TableStats>,
+    /// Additional metrics such as `tokens`, by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: BTreeMap<String, usize>,
+    /// With `--normalize`, whether the file already was in that form.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalized: Option<bool>,
+    /// With `--detect-language`, the likeliest language.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Detection>,
+    /// With `--entities`, counts and most common values of each kind of entity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entities: Option<Entities>,
+    /// With `--estimate`, the confidence intervals behind `stats`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<Estimate>,
+    /// Whether reading stopped before the end of the file, after Ctrl-C.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub partial: bool,
+}
+
+impl Report {
+    pub fn new(files: Vec<FileReport>) -> Self {
+        let total = files
+            .iter()
+            .fold(FileStats::default(), |acc, file| acc + file.stats);
+        let mut metrics = BTreeMap::new();
+        for file in &files {
+            for (name, value) in &file.metrics {
+                *metrics.entry(name.clone()).or_default() += value;
+            }
+        }
+        let entities = files
+            .iter()
+            .filter_map(|file| file.entities.clone())
+            .reduce(|a, b| a + b);
+        Report {
+            files,
+            total,
+            metrics,
+            entities,
+            duplicates: None,
+            timeline: None,
+            violations: Vec::new(),
+            groups: Vec::new(),
+            tree: Vec::new(),
+            errors: Vec::new(),
+            estimate: None,
+            incomplete: false,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read(path)
+            .with_context(|| format!("Failed to read baseline: {}", path.display()))?;
+        serde_json::from_slice(&json)
+            .with_context(|| format!("Failed to parse baseline: {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write baseline: {}", path.display()))
+    }
+}
+
+/// A count that can be selected with `--metrics`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+    Words,
+    Lines,
+    Chars,
+    Bytes,
+}
+
+impl Column {
+    /// The columns shown without `--metrics`.
+    pub const DEFAULT: [Column; 3] = [Column::Words, Column::Lines, Column::Chars];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Column::Words => "words",
+            Column::Lines => "lines",
+            Column::Chars => "chars",
+            Column::Bytes => "bytes",
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Column::Words => "Words",
+            Column::Lines => "Lines",
+            Column::Chars => "Characters",
+            Column::Bytes => "Bytes",
+        }
+    }
+
+    fn width(self) -> usize {
+        match self {
+            Column::Words | Column::Lines => 10,
+            Column::Chars | Column::Bytes => 12,
+        }
+    }
+
+    /// The confidence interval of this count, when it was estimated from samples.
+    pub fn interval(self, estimate: &Estimate) -> Option<Interval> {
+        match self {
+            _ if estimate.exact => None,
+            Column::Words => Some(estimate.words),
+            Column::Lines => Some(estimate.lines),
+            Column::Chars => Some(estimate.chars),
+            Column::Bytes => None,
+        }
+    }
+
+    /// The count in `stats`, or for bytes in `metrics`.
+    pub fn value(self, stats: &FileStats, metrics: &BTreeMap<String, usize>) -> usize {
+        match self {
+            Column::Words => stats.words,
+            Column::Lines => stats.lines,
+            Column::Chars => stats.chars,
+            Column::Bytes => metrics.get("bytes").copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// A table row of the selected columns, then the extra ones, then the name. With
+/// [`Column::DEFAULT`] this is the same as [`format_row_with`].
+pub fn format_columns(
+    columns: &[Column],
+    values: &[String],
+    extra: &[String],
+    name: &str,
+) -> String {
+    let mut row: Vec<String> = columns
+        .iter()
+        .zip(values)
+        .map(|(column, value)| format!("{:>width$}", value, width = column.width()))
+        .collect();
+    row.extend(extra.iter().map(|value| format!("{:>10}", value)));
+    format!("{}  {}", row.join(" "), name)
+}
+
+/// One row of the multi-file table.
+pub fn format_row(
+    words: impl fmt::Display,
+    lines: impl fmt::Display,
+    chars: impl fmt::Display,
+    name: &str,
+) -> String {
+    format_row_with(words, lines, chars, &[], name)
+}
+
+/// A table row with extra columns, e.g. for `--tokens`, between the counts and the name.
+pub fn format_row_with(
+    words: impl fmt::Display,
+    lines: impl fmt::Display,
+    chars: impl fmt::Display,
+    extra: &[String],
+    name: &str,
+) -> String {
+    let extra: String = extra
+        .iter()
+        .map(|value| format!(" {:>10}", value))
+        .collect();
+    format!(
+        "{:>10} {:>10} {:>12}{}  {}",
+        words, lines, chars, extra, name
+    )
+}
+
+pub fn stats_row(stats: &FileStats, name: &str) -> String {
+    format_row(stats.words, stats.lines, stats.chars, name)
+}
+
+/// Formats the counts, followed by the change since `previous` when there is one.
+pub fn format_stats(stats: &FileStats, previous: Option<&FileStats>) -> String {
+    let field = |name: &str, value: usize, old: Option<usize>| match old {
+        Some(old) => format!("{}: {} ({:+})", name, value, value as i64 - old as i64),
+        None => format!("{}: {}", name, value),
+    };
+    [
+        field("Words", stats.words, previous.map(|p| p.words)),
+        field("Lines", stats.lines, previous.map(|p| p.lines)),
+        field("Characters", stats.chars, previous.map(|p| p.chars)),
+    ]
+    .join("  ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_stats_with_delta() {
+        let old = FileStats {
+            words: 10,
+            lines: 3,
+            chars: 60,
+        };
+        let new = FileStats {
+            words: 12,
+            lines: 3,
+            chars: 55,
+        };
+
+        assert_eq!(
+            format_stats(&new, Some(&old)),
+            "Words: 12 (+2)  Lines: 3 (+0)  Characters: 55 (-5)"
+        );
+        assert_eq!(
+            format_stats(&new, None),
+            "Words: 12  Lines: 3  Characters: 55"
+        );
+    }
+
+    #[test]
+    fn test_format_columns() {
+        let values = ["1".to_string(), "2".to_string(), "3".to_string()];
+        assert_eq!(
+            format_columns(&Column::DEFAULT, &values, &["4".to_string()], "a.txt"),
+            format_row_with(1, 2, 3, &["4".to_string()], "a.txt")
+        );
+        assert_eq!(
+            format_columns(&[Column::Lines, Column::Bytes], &values[..2], &[], "a.txt"),
+            "         1            2  a.txt"
+        );
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let missing = anyhow::Error::new(AnalyzerError::NotFound {
+            path: "a.txt".into(),
+        });
+        assert_eq!(ErrorKind::of(&missing), ErrorKind::NotFound);
+
+        let denied = anyhow::Error::new(io::Error::from(io::ErrorKind::PermissionDenied))
+            .context("Failed to read metadata: a.txt");
+        assert_eq!(ErrorKind::of(&denied), ErrorKind::PermissionDenied);
+        assert_eq!(ErrorKind::of(&anyhow::anyhow!("bad")), ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_json_roundtrip() -> Result<()> {
+        let report = Report::new(vec![
+            FileReport {
+                path: "a.txt".to_string(),
+                stats: FileStats {
+                    words: 2,
+                    lines: 1,
+                    chars: 11,
+                },
+                ..Default::default()
+            },
+            FileReport {
+                path: "b.txt".to_string(),
+                stats: FileStats {
+                    words: 1,
+                    lines: 1,
+                    chars: 4,
+                },
+                ..Default::default()
+            },
+        ]);
+        assert_eq!(report.total.words, 3);
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("baseline.json");
+        report.save(&path)?;
+        assert_eq!(Report::load(&path)?, report);
+        Ok(())
+    }
+}
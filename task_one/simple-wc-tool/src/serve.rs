@@ -0,0 +1,367 @@
+use crate::report::{FileReport, Report};
+use crate::{metric, parse_size};
+use anyhow::{Context, Result};
+use memchr::memmem;
+use serde_json::json;
+use simple_wc_tool::analyzer::Analyzer;
+use simple_wc_tool::error::AnalyzerError;
+use std::io::Read;
+use std::sync::Arc;
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Options of the `serve` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct ServeArgs {
+    /// Address to listen on; only reachable from this machine by default
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: String,
+
+    /// Largest request body to accept, e.g. 512K or 64M
+    #[arg(long, value_name = "SIZE", default_value = "10M", value_parser = parse_size)]
+    max_body: u64,
+
+    /// Number of requests handled at the same time
+    #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    threads: u16,
+}
+
+/// Serves `POST /analyze` and `GET /health` until the process is stopped.
+pub fn run(args: &ServeArgs) -> Result<()> {
+    let server = Server::http(&args.addr)
+        .map_err(|e| anyhow::anyhow!(e))
+        .with_context(|| format!("Failed to listen on {}", args.addr))?;
+    match server.server_addr().to_ip() {
+        Some(addr) => eprintln!("Listening on http://{}", addr),
+        None => eprintln!("Listening on {}", args.addr),
+    }
+    serve(Arc::new(server), args.threads.into(), args.max_body);
+    Ok(())
+}
+
+/// Handles requests on `threads` threads, each taking the next request when it is free.
+fn serve(server: Arc<Server>, threads: usize, max_body: u64) {
+    let workers: Vec<_> = (0..threads)
+        .map(|_| {
+            let server = Arc::clone(&server);
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    handle(request, max_body);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+}
+
+/// A failed request, answered with `{"error": message}`.
+#[derive(Debug)]
+struct HttpError {
+    status: u16,
+    message: String,
+}
+
+impl HttpError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        HttpError {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+fn handle(mut request: Request, max_body: u64) {
+    let (status, body) = match route(&mut request, max_body) {
+        Ok(body) => (200, body),
+        Err(err) => (err.status, json!({ "error": err.message }).to_string()),
+    };
+    let header = Header::from_bytes("Content-Type", "application/json").expect("valid header");
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header);
+    // The client may have gone away; there is no one left to tell.
+    let _ = request.respond(response);
+}
+
+fn route(request: &mut Request, max_body: u64) -> Result<String, HttpError> {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    match (request.method(), path) {
+        (Method::Get, "/health") => Ok(json!({ "status": "ok" }).to_string()),
+        (Method::Post, "/analyze") => {
+            let options = Options::from_query(query);
+            let report = analyze_request(request, max_body, &options)?;
+            serde_json::to_string(&report).map_err(|e| HttpError::new(500, e.to_string()))
+        }
+        (_, "/health" | "/analyze") => Err(HttpError::new(405, "method not allowed")),
+        _ => Err(HttpError::new(404, "not found; use POST /analyze")),
+    }
+}
+
+/// What to count besides the defaults, from query parameters like `?language&entities`.
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    detect_language: bool,
+    entities: bool,
+}
+
+impl Options {
+    fn from_query(query: &str) -> Self {
+        let mut options = Options::default();
+        for pair in query.split('&') {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let on = matches!(value, "" | "1" | "true");
+            match key {
+                "language" => options.detect_language = on,
+                "entities" => options.entities = on,
+                _ => {}
+            }
+        }
+        options
+    }
+}
+
+/// Analyzes the body as one text, or every part of a `multipart/form-data` body as a file.
+fn analyze_request(
+    request: &mut Request,
+    max_body: u64,
+    options: &Options,
+) -> Result<Report, HttpError> {
+    let too_large = || {
+        HttpError::new(
+            413,
+            format!("request body is larger than {} bytes", max_body),
+        )
+    };
+    if request
+        .body_length()
+        .is_some_and(|len| len as u64 > max_body)
+    {
+        return Err(too_large());
+    }
+    // The length may be unknown with chunked encoding, so the limit applies while reading too.
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(max_body + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| HttpError::new(400, e.to_string()))?;
+    if body.len() as u64 > max_body {
+        return Err(too_large());
+    }
+
+    let content_type = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Content-Type"))
+        .map(|header| header.value.as_str().to_string())
+        .unwrap_or_default();
+    let files = match multipart_boundary(&content_type) {
+        Some(boundary) => parse_multipart(&body, &boundary)
+            .map_err(|message| HttpError::new(400, message))?
+            .into_iter()
+            .map(|part| analyze(part.filename.unwrap_or(part.name), part.data, options))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => vec![analyze("-".to_string(), &body, options)?],
+    };
+    Ok(Report::new(files))
+}
+
+fn analyze(name: String, data: &[u8], options: &Options) -> Result<FileReport, HttpError> {
+    let mut analyzer = Analyzer::new();
+    analyzer.add_metric(metric::Bytes::default());
+    if options.detect_language {
+        analyzer.detect_language();
+    }
+    if options.entities {
+        analyzer.count_entities();
+    }
+    let analysis = analyzer
+        .update(data)
+        .and_then(|()| analyzer.finish())
+        .map_err(|err| match err {
+            AnalyzerError::InvalidUtf8 { .. } => HttpError::new(422, format!("{}: {}", name, err)),
+            _ => HttpError::new(500, err.to_string()),
+        })?;
+    Ok(FileReport {
+        path: name,
+        stats: analysis.stats,
+        metrics: analysis.metrics,
+        language: analysis.language,
+        entities: analysis.entities,
+        ..Default::default()
+    })
+}
+
+fn multipart_boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';').map(str::trim);
+    if !params.next()?.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params
+        .find_map(|param| param.strip_prefix("boundary="))
+        .map(|boundary| boundary.trim_matches('"').to_string())
+}
+
+#[derive(Debug, PartialEq)]
+struct Part<'a> {
+    name: String,
+    filename: Option<String>,
+    data: &'a [u8],
+}
+
+/// Splits a `multipart/form-data` body into its parts.
+fn parse_multipart<'a>(body: &'a [u8], boundary: &str) -> Result<Vec<Part<'a>>, String> {
+    let malformed = || "malformed multipart body".to_string();
+    let delimiter = format!("--{}", boundary);
+    let separator = format!("\r\n{}", delimiter);
+
+    let start = memmem::find(body, delimiter.as_bytes()).ok_or_else(malformed)?;
+    let mut rest = &body[start + delimiter.len()..];
+    let mut parts = Vec::new();
+    // Every delimiter is followed by a part, except the last one, which ends in `--`.
+    while !rest.starts_with(b"--") {
+        rest = rest.strip_prefix(b"\r\n").ok_or_else(malformed)?;
+        let headers_end = memmem::find(rest, b"\r\n\r\n").ok_or_else(malformed)?;
+        let headers = std::str::from_utf8(&rest[..headers_end]).map_err(|_| malformed())?;
+        rest = &rest[headers_end + 4..];
+        let end = memmem::find(rest, separator.as_bytes()).ok_or_else(malformed)?;
+        let data = &rest[..end];
+        rest = &rest[end + separator.len()..];
+
+        let disposition = headers
+            .split("\r\n")
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.trim()
+                    .eq_ignore_ascii_case("content-disposition")
+                    .then_some(value)
+            })
+            .ok_or_else(|| "multipart part without Content-Disposition".to_string())?;
+        parts.push(Part {
+            name: disposition_param(disposition, "name").unwrap_or_default(),
+            filename: disposition_param(disposition, "filename"),
+            data,
+        });
+    }
+    Ok(parts)
+}
+
+/// A parameter of a `Content-Disposition` header, e.g. `filename` in
+/// `form-data; name="file"; filename="notes.txt"`.
+fn disposition_param(header: &str, key: &str) -> Option<String> {
+    header.split(';').map(str::trim).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        (name == key).then(|| value.trim_matches('"').to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    #[test]
+    fn test_parse_multipart() {
+        let body = b"preamble\r\n--xyz\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n\
+            hello world\r\n\
+            --xyz\r\n\
+            content-disposition: form-data; name=\"note\"\r\n\r\n\
+            one\r\ntwo\r\n\
+            --xyz--\r\n";
+        let parts = parse_multipart(body, "xyz").unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                Part {
+                    name: "file".to_string(),
+                    filename: Some("a.txt".to_string()),
+                    data: b"hello world",
+                },
+                Part {
+                    name: "note".to_string(),
+                    filename: None,
+                    data: b"one\r\ntwo",
+                },
+            ]
+        );
+        assert!(parse_multipart(b"--xyz\r\nno end", "xyz").is_err());
+        assert_eq!(
+            multipart_boundary("multipart/form-data; boundary=\"xyz\"").as_deref(),
+            Some("xyz")
+        );
+        assert_eq!(multipart_boundary("text/plain"), None);
+    }
+
+    /// Sends a raw HTTP request and returns the status code and the body.
+    fn send(addr: &str, request: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn post(addr: &str, path: &str, content_type: &str, body: &str) -> (u16, Value) {
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             Content-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+            path,
+            content_type,
+            body.len(),
+            body
+        );
+        send(addr, &request)
+    }
+
+    #[test]
+    fn test_serves_concurrent_requests() {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let addr = server.server_addr().to_ip().unwrap().to_string();
+        thread::spawn(move || serve(server, 2, 128));
+
+        let clients: Vec<_> = (0..8)
+            .map(|i| {
+                let addr = addr.clone();
+                thread::spawn(move || post(&addr, "/analyze", "text/plain", &"word ".repeat(i + 1)))
+            })
+            .collect();
+        for (i, client) in clients.into_iter().enumerate() {
+            let (status, body) = client.join().unwrap();
+            assert_eq!(status, 200);
+            assert_eq!(body["total"]["words"], i + 1);
+            assert_eq!(body["files"][0]["metrics"]["bytes"], 5 * (i + 1));
+        }
+
+        let multipart =
+            "--b\r\nContent-Disposition: form-data; name=\"f\"; filename=\"x.md\"\r\n\r\n\
+                         Guten Morgen Welt\r\n--b--\r\n";
+        let (status, body) = post(
+            &addr,
+            "/analyze?language",
+            "multipart/form-data; boundary=b",
+            multipart,
+        );
+        assert_eq!(status, 200);
+        assert_eq!(body["files"][0]["path"], "x.md");
+        assert_eq!(body["files"][0]["words"], 3);
+        assert_eq!(body["files"][0]["language"]["language"], "de");
+
+        let (status, body) = post(&addr, "/analyze", "text/plain", &"x".repeat(129));
+        assert_eq!(status, 413);
+        assert!(body["error"].as_str().unwrap().contains("128 bytes"));
+
+        let get = "GET /analyze HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+        assert_eq!(send(&addr, get).0, 405);
+        let health = "GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+        assert_eq!(send(&addr, health), (200, json!({ "status": "ok" })));
+    }
+}
@@ -0,0 +1,434 @@
+//! Line and byte rates of log files over time, from the timestamp at the start of each
+//! line. Lines without one, like the rest of a stack trace, belong to the line before.
+
+use crate::region::{self, Region};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, NaiveDate};
+use clap::ValueEnum;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Most buckets a series may have, counting the empty ones between the first and last.
+const MAX_BUCKETS: i64 = 1_000_000;
+
+/// Busiest buckets to report.
+const PEAKS: usize = 5;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampFormat {
+    /// Whichever of the others the first timestamp of a file is in.
+    Auto,
+    /// `2024-01-31T12:00:00.123+02:00`, also with a space instead of `T` or without offset.
+    Rfc3339,
+    /// `Jan 31 12:00:00`, with the year taken from when the file was last modified.
+    Syslog,
+    /// Common (and combined) log format: `host - user [31/Jan/2024:12:00:00 +0200] ...`.
+    Clf,
+}
+
+impl TimestampFormat {
+    const ALL: [TimestampFormat; 3] = [
+        TimestampFormat::Rfc3339,
+        TimestampFormat::Syslog,
+        TimestampFormat::Clf,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TimestampFormat::Auto => "auto",
+            TimestampFormat::Rfc3339 => "rfc3339",
+            TimestampFormat::Syslog => "syslog",
+            TimestampFormat::Clf => "clf",
+        }
+    }
+
+    fn regex(self) -> &'static Regex {
+        static RFC3339: OnceLock<Regex> = OnceLock::new();
+        static SYSLOG: OnceLock<Regex> = OnceLock::new();
+        static CLF: OnceLock<Regex> = OnceLock::new();
+        let (cell, pattern) = match self {
+            TimestampFormat::Auto | TimestampFormat::Rfc3339 => (
+                &RFC3339,
+                r"^\[?(\d{4})-(\d{2})-(\d{2})[T ](\d{2}):(\d{2}):(\d{2})(?:[.,]\d+)? ?(Z|[+-]\d{2}:?\d{2})?",
+            ),
+            TimestampFormat::Syslog => (
+                &SYSLOG,
+                r"^(Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec) +(\d{1,2}) (\d{2}):(\d{2}):(\d{2})",
+            ),
+            TimestampFormat::Clf => (
+                &CLF,
+                r"^\S+ \S+ \S+ \[(\d{2})/(\w{3})/(\d{4}):(\d{2}):(\d{2}):(\d{2}) ([+-]\d{4})\]",
+            ),
+        };
+        cell.get_or_init(|| Regex::new(pattern).expect("timestamp pattern is valid"))
+    }
+}
+
+/// Parses `5m`, `1h`, `30s`, `1d` or a plain number of seconds.
+pub fn parse_duration(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => s.split_at(pos),
+        None => (s, "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => 0,
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        // Bucket starts are computed in signed seconds.
+        .filter(|&seconds| seconds > 0 && seconds <= i64::MAX as u64)
+        .ok_or_else(|| format!("'{}' is not a duration like 30s, 5m, 1h or 1d", s))
+}
+
+#[derive(Debug, Clone)]
+pub struct TimelineOptions {
+    pub format: TimestampFormat,
+    /// Width of each bucket in seconds.
+    pub bucket: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bucket {
+    /// Start of the bucket in UTC, as RFC 3339.
+    pub start: String,
+    pub lines: usize,
+    /// Bytes of the lines, including their line breaks.
+    pub bytes: usize,
+}
+
+/// A run of empty buckets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Gap {
+    pub start: String,
+    pub end: String,
+    pub seconds: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Timeline {
+    /// The format the timestamps were read in, `None` if no line had one.
+    pub format: Option<TimestampFormat>,
+    pub bucket_seconds: u64,
+    /// Every bucket from the first timestamp to the last, including empty ones.
+    pub buckets: Vec<Bucket>,
+    /// Buckets with the most lines, busiest first.
+    pub peaks: Vec<Bucket>,
+    pub gaps: Vec<Gap>,
+    /// Lines before the first timestamp of their file, which no bucket holds.
+    pub untimed_lines: usize,
+}
+
+/// Reads the timestamps of one file, keeping track of the year syslog lines leave out.
+struct LineParser {
+    format: TimestampFormat,
+    /// Year and month the file was last modified in.
+    modified: (i32, u32),
+    /// Year and month of the last syslog line, `None` before the first.
+    current: Option<(i32, u32)>,
+}
+
+impl LineParser {
+    fn new(format: TimestampFormat, modified: (i32, u32)) -> Self {
+        LineParser {
+            format,
+            modified,
+            current: None,
+        }
+    }
+
+    /// Seconds since the epoch in UTC. Timestamps without an offset are taken as UTC.
+    fn parse(&mut self, line: &str) -> Option<i64> {
+        if self.format == TimestampFormat::Auto {
+            let format = TimestampFormat::ALL
+                .into_iter()
+                .find(|format| format.regex().is_match(line))?;
+            self.format = format;
+        }
+        let caps = self.format.regex().captures(line)?;
+        let number = |i: usize| caps[i].parse::<u32>().ok();
+        let month = |name: &str| MONTHS.iter().position(|&m| m == name).map(|i| i as u32 + 1);
+
+        let (date, time, offset) = match self.format {
+            TimestampFormat::Auto | TimestampFormat::Rfc3339 => {
+                let year = caps[1].parse().ok()?;
+                let date = NaiveDate::from_ymd_opt(year, number(2)?, number(3)?)?;
+                let offset = caps.get(7).map_or(Some(0), |m| parse_offset(m.as_str()))?;
+                (date, clock(&caps, 4)?, offset)
+            }
+            TimestampFormat::Syslog => {
+                let month = month(&caps[1])?;
+                let year = self.syslog_year(month);
+                self.current = Some((year, month));
+                let date = NaiveDate::from_ymd_opt(year, month, number(2)?)?;
+                (date, clock(&caps, 3)?, 0)
+            }
+            TimestampFormat::Clf => {
+                let year = caps[3].parse().ok()?;
+                let date = NaiveDate::from_ymd_opt(year, month(&caps[2])?, number(1)?)?;
+                (date, clock(&caps, 4)?, parse_offset(&caps[7])?)
+            }
+        };
+        let time = date.and_hms_opt(time.0, time.1, time.2)?;
+        Some(time.and_utc().timestamp() - offset)
+    }
+
+    /// A log whose first line is from a later month than the file was last modified in
+    /// started the year before. After that, logs run forward, so a smaller month means
+    /// the year turned.
+    fn syslog_year(&self, month: u32) -> i32 {
+        match self.current {
+            None if month > self.modified.1 => self.modified.0 - 1,
+            None => self.modified.0,
+            Some((year, previous)) if month < previous => year + 1,
+            Some((year, _)) => year,
+        }
+    }
+}
+
+/// Hours, minutes and seconds from three consecutive groups.
+fn clock(caps: &Captures, first: usize) -> Option<(u32, u32, u32)> {
+    let number = |i: usize| caps[first + i].parse::<u32>().ok();
+    Some((number(0)?, number(1)?, number(2)?))
+}
+
+/// Seconds east of UTC, from `Z`, `+02:00` or `-0700`.
+fn parse_offset(s: &str) -> Option<i64> {
+    if s == "Z" {
+        return Some(0);
+    }
+    let sign = if s.starts_with('-') { -1 } else { 1 };
+    let digits: String = s[1..].chars().filter(char::is_ascii_digit).collect();
+    let hours: i64 = digits.get(..2)?.parse().ok()?;
+    let minutes: i64 = digits.get(2..4)?.parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+fn format_time(seconds: i64) -> String {
+    DateTime::from_timestamp(seconds, 0)
+        .map(|time| time.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .unwrap_or_default()
+}
+
+/// Buckets lines and bytes by the timestamps of the lines, over any number of inputs.
+#[derive(Debug)]
+pub struct TimelineCounter {
+    options: TimelineOptions,
+    format: Option<TimestampFormat>,
+    /// Lines and bytes by bucket number, counted from the epoch.
+    buckets: BTreeMap<i64, (usize, usize)>,
+    untimed_lines: usize,
+}
+
+impl TimelineCounter {
+    pub fn new(options: TimelineOptions) -> Self {
+        TimelineCounter {
+            options,
+            format: None,
+            buckets: BTreeMap::new(),
+            untimed_lines: 0,
+        }
+    }
+
+    /// Counts the lines of one input. `modified` is the year and month it was last
+    /// written in, which syslog timestamps are assumed to lead up to.
+    pub fn add_lines(
+        &mut self,
+        mut reader: impl BufRead,
+        modified: (i32, u32),
+    ) -> std::io::Result<()> {
+        let mut parser = LineParser::new(self.options.format, modified);
+        let mut current = None;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let text = String::from_utf8_lossy(&line);
+            if let Some(seconds) = parser.parse(&text) {
+                current = Some(seconds.div_euclid(self.options.bucket as i64));
+                self.format.get_or_insert(parser.format);
+            }
+            match current {
+                Some(bucket) => {
+                    let entry = self.buckets.entry(bucket).or_default();
+                    entry.0 += 1;
+                    entry.1 += line.len();
+                }
+                None => self.untimed_lines += 1,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<Timeline> {
+        let width = self.options.bucket as i64;
+        let (first, last) = match (
+            self.buckets.first_key_value(),
+            self.buckets.last_key_value(),
+        ) {
+            (Some((&first, _)), Some((&last, _))) => (first, last),
+            _ => (0, -1),
+        };
+        if last - first >= MAX_BUCKETS {
+            anyhow::bail!(
+                "Timestamps span {} buckets of {}s; use a larger --bucket",
+                last - first + 1,
+                width
+            );
+        }
+
+        let buckets: Vec<Bucket> = (first..=last)
+            .map(|n| {
+                let (lines, bytes) = self.buckets.get(&n).copied().unwrap_or_default();
+                Bucket {
+                    start: format_time(n * width),
+                    lines,
+                    bytes,
+                }
+            })
+            .collect();
+
+        // Ties go to the earlier bucket, so the output is stable.
+        let mut peaks: Vec<Bucket> = buckets.iter().filter(|b| b.lines > 0).cloned().collect();
+        peaks.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.start.cmp(&b.start)));
+        peaks.truncate(PEAKS);
+
+        let mut gaps = Vec::new();
+        let mut previous = None;
+        for &n in self.buckets.keys() {
+            if let Some(previous) = previous.filter(|&p| n > p + 1) {
+                gaps.push(Gap {
+                    start: format_time((previous + 1) * width),
+                    end: format_time(n * width),
+                    seconds: ((n - previous - 1) * width) as u64,
+                });
+            }
+            previous = Some(n);
+        }
+
+        Ok(Timeline {
+            format: self.format,
+            bucket_seconds: self.options.bucket,
+            buckets,
+            peaks,
+            gaps,
+            untimed_lines: self.untimed_lines,
+        })
+    }
+}
+
+/// Reads files line by line and buckets their lines by timestamp, over all of them.
+pub fn analyze_files(
+    paths: &[PathBuf],
+    options: &TimelineOptions,
+    region: Option<&Region>,
+) -> Result<Timeline> {
+    let mut counter = TimelineCounter::new(options.clone());
+    for path in paths {
+        let file = region::open(path, region)?;
+        counter
+            .add_lines(BufReader::new(file), modified_month(path))
+            .with_context(|| format!("Failed to read line from file: {}", path.display()))?;
+    }
+    counter.finish()
+}
+
+fn modified_month(path: &Path) -> (i32, u32) {
+    let modified = fs::metadata(path).and_then(|metadata| metadata.modified());
+    let time = modified.map_or(DateTime::UNIX_EPOCH, DateTime::<chrono::Utc>::from);
+    (time.year(), time.month())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(input: &str, format: TimestampFormat, bucket: u64) -> Timeline {
+        let mut counter = TimelineCounter::new(TimelineOptions { format, bucket });
+        counter.add_lines(input.as_bytes(), (2024, 3)).unwrap();
+        counter.finish().unwrap()
+    }
+
+    #[test]
+    fn test_parses_formats() {
+        let parse = |line: &str| LineParser::new(TimestampFormat::Auto, (2024, 3)).parse(line);
+        let expected = Some(1_706_702_400); // 2024-01-31T12:00:00Z
+        assert_eq!(parse("2024-01-31T12:00:00Z started"), expected);
+        assert_eq!(parse("2024-01-31 14:00:00.250+02:00 started"), expected);
+        assert_eq!(parse("[2024-01-31T12:00:00] started"), expected);
+        assert_eq!(parse("Jan 31 12:00:00 host sshd[42]: started"), expected);
+        assert_eq!(
+            parse(r#"10.0.0.1 - - [31/Jan/2024:05:00:00 -0700] "GET / HTTP/1.1" 200 512"#),
+            expected
+        );
+        assert_eq!(parse("  at Main.run(Main.java:12)"), None);
+
+        // A syslog file modified in March that starts in December began the year before.
+        let mut parser = LineParser::new(TimestampFormat::Syslog, (2024, 3));
+        let december = parser.parse("Dec 31 23:00:00 x").unwrap();
+        let january = parser.parse("Jan  1 01:00:00 x").unwrap();
+        assert_eq!(january - december, 2 * 3600);
+
+        assert_eq!(parse_duration("5m"), Ok(300));
+        assert_eq!(parse_duration("90"), Ok(90));
+        assert!(parse_duration("0h").is_err());
+        assert!(parse_duration("5 weeks").is_err());
+        assert!(parse_duration("300000000000000000d").is_err());
+        assert!(parse_duration(&(i64::MAX as u64 + 1).to_string()).is_err());
+    }
+
+    #[test]
+    fn test_buckets_peaks_and_gaps() {
+        let input = "\
+preamble
+2024-01-31T12:00:10Z a
+2024-01-31T12:00:50Z b
+  continued
+2024-01-31T12:01:30Z c
+2024-01-31T12:04:00Z d
+";
+        let timeline = count(input, TimestampFormat::Auto, 60);
+        assert_eq!(timeline.format, Some(TimestampFormat::Rfc3339));
+        assert_eq!(timeline.untimed_lines, 1);
+        let counts: Vec<(usize, usize)> = timeline
+            .buckets
+            .iter()
+            .map(|b| (b.lines, b.bytes))
+            .collect();
+        assert_eq!(counts, vec![(3, 58), (1, 23), (0, 0), (0, 0), (1, 23)]);
+        assert_eq!(timeline.buckets[0].start, "2024-01-31T12:00:00Z");
+        assert_eq!(timeline.peaks[0].start, "2024-01-31T12:00:00Z");
+        assert_eq!(timeline.peaks.len(), 3);
+        assert_eq!(
+            timeline.gaps,
+            vec![Gap {
+                start: "2024-01-31T12:02:00Z".to_string(),
+                end: "2024-01-31T12:04:00Z".to_string(),
+                seconds: 120,
+            }]
+        );
+
+        let empty = count("no timestamps\n", TimestampFormat::Auto, 60);
+        assert_eq!(empty.format, None);
+        assert!(empty.buckets.is_empty());
+        assert_eq!(empty.untimed_lines, 1);
+    }
+}
@@ -0,0 +1,366 @@
+use crate::metric::Metric;
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use regex::Regex;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+/// Pre-tokens whose counts are remembered; most text repeats a small set of words.
+const CACHE_LIMIT: usize = 100_000;
+
+/// A byte-level BPE vocabulary: every byte sequence that can become a single token,
+/// with its rank. Lower ranks are merged first.
+///
+/// Three file formats are understood:
+/// - tiktoken rank files (`cl100k_base.tiktoken`): one `BASE64 RANK` pair per line;
+/// - GPT-2 style merge lists (`merges.txt`): one `LEFT RIGHT` pair per line, in merge order;
+/// - JSON vocabularies mapping tokens to ids (`vocab.json`), or a `tokenizer.json` with
+///   one under `model.vocab`; ids are used as ranks.
+///
+/// The last two use GPT-2's mapping of bytes to printable characters (`Ġ` for a space).
+#[derive(Debug, Default)]
+pub struct Vocabulary {
+    ranks: HashMap<Vec<u8>, u32>,
+    /// BLAKE3 hash of the text the vocabulary was parsed from, in hex.
+    hash: String,
+}
+
+impl Vocabulary {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read vocabulary: {}", path.display()))?;
+        Self::parse(&text)
+            .with_context(|| format!("Failed to parse vocabulary: {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let trimmed = text.trim_start();
+        let mut vocabulary = if trimmed.starts_with('{') {
+            Self::parse_json(trimmed)?
+        } else if is_tiktoken(trimmed) {
+            Self::parse_tiktoken(trimmed)?
+        } else {
+            Self::parse_merges(trimmed)?
+        };
+        if vocabulary.ranks.is_empty() {
+            bail!("no tokens found");
+        }
+        vocabulary.hash = blake3::hash(text.as_bytes()).to_hex().to_string();
+        Ok(vocabulary)
+    }
+
+    /// Identifies the vocabulary by its file's content, e.g. in cache keys.
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    fn parse_tiktoken(text: &str) -> Result<Self> {
+        let mut ranks = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let parsed = line.split_once(' ').and_then(|(token, rank)| {
+                Some((BASE64.decode(token).ok()?, rank.trim().parse().ok()?))
+            });
+            let Some((token, rank)) = parsed else {
+                bail!("line {}: expected 'BASE64 RANK'", number + 1);
+            };
+            ranks.insert(token, rank);
+        }
+        Ok(Vocabulary {
+            ranks,
+            ..Default::default()
+        })
+    }
+
+    fn parse_merges(text: &str) -> Result<Self> {
+        let mut ranks = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            if line.starts_with("#version") || line.trim().is_empty() {
+                continue;
+            }
+            let Some((left, right)) = line.split_once(' ') else {
+                bail!("line {}: expected 'LEFT RIGHT'", number + 1);
+            };
+            let mut token = decode_symbols(left)?;
+            token.extend(decode_symbols(right)?);
+            let rank = ranks.len() as u32;
+            ranks.entry(token).or_insert(rank);
+        }
+        Ok(Vocabulary {
+            ranks,
+            ..Default::default()
+        })
+    }
+
+    fn parse_json(text: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(text)?;
+        let vocab = value
+            .pointer("/model/vocab")
+            .unwrap_or(&value)
+            .as_object()
+            .context("expected an object mapping tokens to ids")?;
+
+        let mut ranks = HashMap::new();
+        for (token, id) in vocab {
+            let id = id.as_u64().context("token ids must be numbers")?;
+            // Special tokens like <|endoftext|> don't use the byte mapping and never
+            // come out of merging anyway.
+            if let Ok(token) = decode_symbols(token) {
+                ranks.insert(token, id as u32);
+            }
+        }
+        Ok(Vocabulary {
+            ranks,
+            ..Default::default()
+        })
+    }
+
+    /// Number of tokens `text` is encoded to.
+    pub fn count(&self, text: &str) -> usize {
+        pre_tokenize(text)
+            .map(|piece| self.count_piece(piece.as_bytes()))
+            .sum()
+    }
+
+    fn count_piece(&self, piece: &[u8]) -> usize {
+        if piece.len() < 2 || self.ranks.contains_key(piece) {
+            return piece.len().min(1);
+        }
+
+        // Start from single bytes and keep merging the adjacent pair whose
+        // concatenation has the lowest rank, the leftmost one on a tie. Parts are
+        // known by their start: `ends[start]` is where a part ends, 0 once merged away.
+        let n = piece.len();
+        let mut ends: Vec<usize> = (1..=n).collect();
+        let mut starts: Vec<usize> = (0..n).map(|i| i.saturating_sub(1)).collect();
+        let mut parts = n;
+
+        // Candidate merges as (rank, left, middle, end); entries whose parts have
+        // changed since are skipped when they come up.
+        let mut heap = BinaryHeap::new();
+        let push = |heap: &mut BinaryHeap<_>, left: usize, middle: usize, end: usize| {
+            if let Some(&rank) = self.ranks.get(&piece[left..end]) {
+                heap.push(Reverse((rank, left, middle, end)));
+            }
+        };
+        for i in 1..n {
+            push(&mut heap, i - 1, i, i + 1);
+        }
+        while let Some(Reverse((_, left, middle, end))) = heap.pop() {
+            if ends[left] != middle || ends[middle] != end {
+                continue;
+            }
+            ends[left] = end;
+            ends[middle] = 0;
+            parts -= 1;
+            if left > 0 {
+                let before = starts[left];
+                push(&mut heap, before, left, end);
+            }
+            if end < n {
+                starts[end] = left;
+                push(&mut heap, left, end, ends[end]);
+            }
+        }
+        parts
+    }
+}
+
+fn is_tiktoken(text: &str) -> bool {
+    text.lines().next().is_some_and(|line| {
+        line.split_once(' ').is_some_and(|(token, rank)| {
+            rank.trim().parse::<u32>().is_ok() && BASE64.decode(token).is_ok()
+        })
+    })
+}
+
+/// GPT-2 maps every byte to a printable character, so that tokens can be written
+/// as text: printable Latin-1 bytes map to themselves, the rest to U+0100 and up.
+fn byte_to_char() -> &'static [char; 256] {
+    static TABLE: OnceLock<[char; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let printable = |b: u8| matches!(b, b'!'..=b'~' | 0xA1..=0xAC | 0xAE..=0xFF);
+        let mut table = ['\0'; 256];
+        let mut next = 256;
+        for byte in 0..=255u8 {
+            table[byte as usize] = if printable(byte) {
+                byte as char
+            } else {
+                next += 1;
+                char::from_u32(next - 1).unwrap_or('\0')
+            };
+        }
+        table
+    })
+}
+
+fn decode_symbols(token: &str) -> Result<Vec<u8>> {
+    static TABLE: OnceLock<HashMap<char, u8>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        byte_to_char()
+            .iter()
+            .enumerate()
+            .map(|(byte, &c)| (c, byte as u8))
+            .collect()
+    });
+    token
+        .chars()
+        .map(|c| {
+            table
+                .get(&c)
+                .copied()
+                .with_context(|| format!("'{}' is not a byte-level BPE symbol", c))
+        })
+        .collect()
+}
+
+fn pre_token_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"'(?i:[sdmt]|ll|ve|re)| ?\p{L}+| ?\p{N}+| ?[^\s\p{L}\p{N}]+|\s+")
+            .expect("pre-token pattern is valid")
+    })
+}
+
+/// Splits text the way GPT-2 does before applying merges: contractions, words,
+/// numbers and punctuation, each with an optional leading space.
+fn pre_tokenize(text: &str) -> impl Iterator<Item = &str> {
+    let re = pre_token_regex();
+    let mut start = 0;
+    std::iter::from_fn(move || {
+        let found = re.find_at(text, start)?;
+        let mut end = found.end();
+        // GPT-2 uses `\s+(?!\S)`: a run of whitespace leaves its last character to the
+        // following word, which the regex crate can't express with a lookahead.
+        let piece = found.as_str();
+        if piece.trim().is_empty() && end < text.len() {
+            if let Some((last, _)) = piece.char_indices().last().filter(|&(i, _)| i > 0) {
+                end = found.start() + last;
+            }
+        }
+        start = end;
+        Some(&text[found.start()..end])
+    })
+}
+
+/// Token count as a [`Metric`], named `tokens`.
+pub struct Tokens {
+    vocabulary: Arc<Vocabulary>,
+    count: usize,
+    cache: HashMap<String, usize>,
+}
+
+impl Tokens {
+    pub fn new(vocabulary: Arc<Vocabulary>) -> Self {
+        Tokens {
+            vocabulary,
+            count: 0,
+            cache: HashMap::new(),
+        }
+    }
+}
+
+impl Metric for Tokens {
+    fn name(&self) -> &str {
+        "tokens"
+    }
+
+    fn chars(&mut self, text: &str) {
+        for piece in pre_tokenize(text) {
+            self.count += match self.cache.get(piece) {
+                Some(&count) => count,
+                None => {
+                    let count = self.vocabulary.count_piece(piece.as_bytes());
+                    if self.cache.len() < CACHE_LIMIT {
+                        self.cache.insert(piece.to_string(), count);
+                    }
+                    count
+                }
+            };
+        }
+    }
+
+    /// Keeps the cache of piece counts, which only depends on the vocabulary.
+    fn reset(&mut self) {
+        self.count = 0;
+    }
+
+    fn value(&self) -> usize {
+        self.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiktoken(tokens: &[&str]) -> Vocabulary {
+        let lines: Vec<String> = tokens
+            .iter()
+            .enumerate()
+            .map(|(rank, token)| format!("{} {}", BASE64.encode(token), rank))
+            .collect();
+        Vocabulary::parse(&lines.join("\n")).unwrap()
+    }
+
+    #[test]
+    fn test_pre_tokenize() {
+        let pieces: Vec<_> = pre_tokenize("Hello,  world! It's 42").collect();
+        assert_eq!(
+            pieces,
+            vec!["Hello", ",", " ", " world", "!", " It", "'s", " 42"]
+        );
+    }
+
+    #[test]
+    fn test_merges_follow_rank_order() {
+        let vocabulary = tiktoken(&["lo", "he", "hel", "hello", " w", "or", " wor", "ld"]);
+        assert_eq!(vocabulary.count("hello"), 1);
+        // " world" -> " w" + "or" + "ld" -> " wor" + "ld"
+        assert_eq!(vocabulary.count(" world"), 2);
+        // "help" -> "hel" + "p"
+        assert_eq!(vocabulary.count("help"), 2);
+        assert_eq!(vocabulary.count("hello world"), 3);
+        assert_eq!(vocabulary.count(""), 0);
+    }
+
+    #[test]
+    fn test_long_piece() {
+        // A single pre-token of 200k bytes; merging pair by pair rescans would never end.
+        let vocabulary = tiktoken(&["aa", "aaaa", "ab"]);
+        let text = "a".repeat(200_000);
+        assert_eq!(vocabulary.count(&text), 50_000);
+        assert_eq!(vocabulary.count(&format!("{}b", text)), 50_001);
+        // Ties go to the leftmost pair: "aaa" -> "aa" + "a".
+        assert_eq!(vocabulary.count("aaab"), 2);
+    }
+
+    #[test]
+    fn test_merges_and_json_formats() {
+        let merges = Vocabulary::parse("#version: 0.2\nh e\nl l\nhe ll\nĠ w\n").unwrap();
+        assert_eq!(merges.count("hell"), 1);
+        assert_eq!(merges.count("hello w"), 3);
+
+        let json = Vocabulary::parse(r#"{"model": {"vocab": {"h": 0, "e": 1, "he": 2}}}"#);
+        assert_eq!(json.unwrap().count("hehe"), 2);
+
+        assert!(Vocabulary::parse("").is_err());
+        assert!(Vocabulary::parse("{\"a\": \"b\"}").is_err());
+    }
+
+    #[test]
+    fn test_tokens_metric() {
+        let vocabulary = Arc::new(tiktoken(&["he", "llo", "hello", " hello", "\n"]));
+        let mut analyzer = crate::analyzer::Analyzer::new();
+        analyzer.add_metric(Tokens::new(vocabulary));
+        analyzer.update(b"hello\nhello hello\n").unwrap();
+        // "hello", "\n", "hello", " hello"
+        assert_eq!(analyzer.finish().unwrap().metrics["tokens"], 4);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::analyzer::FileStats;
+use crate::input;
+use crate::report::format_stats;
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Editors usually save in several steps (truncate, write, rename);
+/// events arriving within this window are handled together.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+enum Target {
+    File(PathBuf),
+    Dir(PathBuf),
+}
+
+/// Watches files and directories, counting files with `analyze` whenever they change.
+///
+/// Runs until the process is interrupted. Paths are canonicalized so that
+/// notification paths can be matched against them.
+pub fn run(
+    paths: &[PathBuf],
+    recursive: bool,
+    analyze: impl Fn(&Path) -> Result<FileStats>,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("Failed to start file watcher")?;
+
+    let mut targets = Vec::new();
+    for path in paths {
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("Failed to watch: {}", path.display()))?;
+
+        // Files are watched through their parent directory, so that saves which replace
+        // the file (write to a temporary file, then rename) are still noticed.
+        let (watched, mode) = if canonical.is_dir() {
+            let mode = if recursive {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+            (canonical.clone(), mode)
+        } else {
+            let parent = canonical.parent().unwrap_or(Path::new("/")).to_path_buf();
+            (parent, RecursiveMode::NonRecursive)
+        };
+        watcher
+            .watch(&watched, mode)
+            .with_context(|| format!("Failed to watch: {}", path.display()))?;
+
+        targets.push(if canonical.is_dir() {
+            Target::Dir(canonical)
+        } else {
+            Target::File(canonical)
+        });
+    }
+
+    // A file that can't be read is reported like in the loop below, and picked up once it
+    // changes.
+    let mut snapshot = BTreeMap::new();
+    for file in input::collect_files(paths, recursive)? {
+        let analyzed = file
+            .canonicalize()
+            .with_context(|| format!("Failed to watch: {}", file.display()))
+            .and_then(|file| Ok((analyze(&file)?, file)));
+        match analyzed {
+            Ok((stats, file)) => {
+                println!("{}: {}", display_path(&file), format_stats(&stats, None));
+                snapshot.insert(file, stats);
+            }
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+    print_total(&snapshot, None);
+
+    loop {
+        let mut changed = BTreeSet::new();
+        let first = rx.recv().context("File watcher stopped")?;
+        collect_changes(first, &mut changed);
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            collect_changes(event, &mut changed);
+        }
+
+        let previous_total = total(&snapshot);
+        let mut any = false;
+        for path in changed {
+            if !is_tracked(&path, &targets, recursive) {
+                continue;
+            }
+
+            if path.is_file() {
+                match analyze(&path) {
+                    Ok(stats) => {
+                        let old = snapshot.insert(path.clone(), stats);
+                        if old == Some(stats) {
+                            continue;
+                        }
+                        println!(
+                            "{}: {}",
+                            display_path(&path),
+                            format_stats(&stats, old.as_ref())
+                        );
+                        any = true;
+                    }
+                    Err(e) => eprintln!("{:#}", e),
+                }
+            } else if snapshot.remove(&path).is_some() {
+                println!("{}: removed", display_path(&path));
+                any = true;
+            }
+        }
+
+        if any {
+            print_total(&snapshot, Some(&previous_total));
+        }
+    }
+}
+
+fn collect_changes(event: notify::Result<notify::Event>, changed: &mut BTreeSet<PathBuf>) {
+    match event {
+        // Reading a file produces access events; reacting to them would loop forever.
+        Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+        Ok(event) => changed.extend(event.paths),
+        Err(e) => eprintln!("File watcher error: {}", e),
+    }
+}
+
+fn is_tracked(path: &Path, targets: &[Target], recursive: bool) -> bool {
+    targets.iter().any(|target| match target {
+        Target::File(file) => path == file,
+        Target::Dir(dir) => match path.strip_prefix(dir) {
+            Ok(relative) => {
+                let depth = relative.components().count();
+                (recursive || depth == 1)
+                    && !relative
+                        .components()
+                        .any(|c| input::is_hidden(Path::new(c.as_os_str())))
+            }
+            Err(_) => false,
+        },
+    })
+}
+
+fn total(snapshot: &BTreeMap<PathBuf, FileStats>) -> FileStats {
+    snapshot
+        .values()
+        .fold(FileStats::default(), |acc, &stats| acc + stats)
+}
+
+fn print_total(snapshot: &BTreeMap<PathBuf, FileStats>, previous: Option<&FileStats>) {
+    if snapshot.len() > 1 {
+        println!("total: {}", format_stats(&total(snapshot), previous));
+    }
+}
+
+fn display_path(path: &Path) -> String {
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_tracked() {
+        let targets = vec![
+            Target::File(PathBuf::from("/docs/notes.md")),
+            Target::Dir(PathBuf::from("/book")),
+        ];
+
+        assert!(is_tracked(Path::new("/docs/notes.md"), &targets, false));
+        assert!(!is_tracked(Path::new("/docs/other.md"), &targets, false));
+        assert!(is_tracked(Path::new("/book/ch1.md"), &targets, false));
+        assert!(!is_tracked(Path::new("/book/.ch1.md.swp"), &targets, false));
+        assert!(!is_tracked(
+            Path::new("/book/part1/ch1.md"),
+            &targets,
+            false
+        ));
+        assert!(is_tracked(Path::new("/book/part1/ch1.md"), &targets, true));
+    }
+}